@@ -0,0 +1,43 @@
+//! Tendermint accounts
+
+/// Size of an account ID in bytes
+pub const LENGTH: usize = 20;
+
+/// Account IDs, ie. the addresses of the validators
+#[derive(Copy, Clone)] // Eq, PartialEq cause a crash
+pub struct Id([u8; LENGTH]);
+
+impl Id {
+    /// Create a new account ID from raw bytes
+    pub fn new(bytes: [u8; LENGTH]) -> Id {
+        Id(bytes)
+    }
+
+    /// Borrow the account ID as a byte slice
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0[..]
+    }
+}
+
+impl PartialEq for Id {
+    fn eq(&self, other: &Self) -> bool {
+        let mut i = 0;
+        let mut cont_loop = i < LENGTH;
+        let mut res = true;
+        #[invariant="i >= 0"]
+        #[invariant="cont_loop ==> i < LENGTH"]
+        #[invariant="!cont_loop ==> i >= LENGTH || !res"]
+        while cont_loop {
+            assert!(self.0.len() == LENGTH);
+            assert!(other.0.len() == LENGTH);
+            if self.0[i] != other.0[i] {
+                res = false;
+            }
+            i += 1;
+            cont_loop = i < LENGTH && res;
+        }
+        res
+    }
+}
+
+impl Eq for Id {}
@@ -1,7 +1,14 @@
+//! Errors of the cryptographic and parsing operations of the crate.
+//! They convert into light client errors, see [`lite::error::Kind::Crypto`].
+
+use std::error::Error as StdError;
+use std::fmt;
+
 /// Error type
 pub type Error = Kind;
 
 /// Kinds of errors
+#[derive(Copy, Clone, Debug)]
 pub enum Kind {
     /// Cryptographic operation failed
     Crypto,
@@ -26,4 +33,23 @@ pub enum Kind {
 
     /// Signature invalid
     SignatureInvalid,
-}
\ No newline at end of file
+}
+
+impl fmt::Display for Kind {
+    #[trusted]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match self {
+            Kind::Crypto => "cryptographic error",
+            Kind::InvalidKey => "invalid key",
+            Kind::Io => "I/O error",
+            Kind::Length => "invalid length",
+            Kind::Parse => "parse error",
+            Kind::Protocol => "protocol error",
+            Kind::OutOfRange => "value out of range",
+            Kind::SignatureInvalid => "invalid signature",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+impl StdError for Kind {}
@@ -62,4 +62,22 @@ impl PartialEq for Hash {
     }
 }
 
-impl Eq for Hash {}
\ No newline at end of file
+impl Eq for Hash {}
+
+impl Debug for Hash {
+    #[trusted]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Hash::Sha256({})", self)
+    }
+}
+
+/// The hash is displayed as upper case hexadecimal.
+impl Display for Hash {
+    #[trusted]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in self.0.iter() {
+            write!(f, "{:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
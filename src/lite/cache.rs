@@ -0,0 +1,186 @@
+//! A [`Requester`] adapter which memoizes the responses of another requester.
+//!
+//! Bisection requests the same heights several times: the validator set at
+//! `h+1` is fetched as the next validators of `h` and again as the validators
+//! of `h+1`, and a failed skip attempt re-fetches the header once the pivot
+//! has been verified. Wrapping the requester in a [`CachingRequester`] turns
+//! these repeated requests into cache hits.
+
+use std::cell::{Cell, RefCell};
+
+use lite::error::Error;
+use lite::types::{Commit, Header, Height, Requester, SignedHeader, ValidatorSetImpl};
+
+/// A bounded, least-recently-used map from heights to values.
+/// The entries are kept ordered by recency: the last entry is the
+/// most recently used one, the first entry is evicted first.
+struct Lru<V> {
+    capacity: usize,
+    entries: Vec<(Height, V)>,
+}
+
+impl<V: Clone> Lru<V> {
+    #[trusted]
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Vec::new(),
+        }
+    }
+
+    #[trusted]
+    fn position(&self, h: Height) -> Option<usize> {
+        let mut i = 0;
+        while i < self.entries.len() {
+            if self.entries[i].0 == h {
+                return Some(i);
+            }
+            i += 1;
+        }
+        None
+    }
+
+    /// Returns the value stored for height h, if any, and marks it
+    /// as the most recently used entry.
+    #[trusted]
+    fn get(&mut self, h: Height) -> Option<V> {
+        match self.position(h) {
+            Some(i) => {
+                let entry = self.entries.remove(i);
+                let value = entry.1.clone();
+                self.entries.push(entry);
+                Some(value)
+            }
+            None => None,
+        }
+    }
+
+    /// Stores the value for height h, evicting the least recently used
+    /// entry if the capacity is exceeded.
+    #[trusted]
+    fn insert(&mut self, h: Height, value: V) {
+        if let Some(i) = self.position(h) {
+            self.entries.remove(i);
+        }
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity {
+            self.entries.remove(0);
+        }
+        self.entries.push((h, value));
+    }
+
+    #[trusted]
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// CachingRequester wraps a [`Requester`] and memoizes the signed headers and
+/// validator sets it returns, keyed by height. Each kind of response is kept
+/// in its own LRU cache holding at most `capacity` entries.
+///
+/// Failed requests are not cached.
+pub struct CachingRequester<C, H, R>
+where
+    C: Commit,
+    H: Header,
+    R: Requester<C, H>,
+{
+    inner: R,
+    signed_headers: RefCell<Lru<SignedHeader<C, H>>>,
+    validator_sets: RefCell<Lru<ValidatorSetImpl>>,
+    hits: Cell<u64>,
+    misses: Cell<u64>,
+}
+
+impl<C, H, R> CachingRequester<C, H, R>
+where
+    C: Commit,
+    H: Header,
+    R: Requester<C, H>,
+{
+    /// Wrap the given requester, caching at most `capacity` signed headers
+    /// and `capacity` validator sets.
+    pub fn new(inner: R, capacity: usize) -> Self {
+        Self {
+            inner,
+            signed_headers: RefCell::new(Lru::new(capacity)),
+            validator_sets: RefCell::new(Lru::new(capacity)),
+            hits: Cell::new(0),
+            misses: Cell::new(0),
+        }
+    }
+
+    /// The wrapped requester.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// Number of requests answered from the cache.
+    #[trusted]
+    pub fn hits(&self) -> u64 {
+        self.hits.get()
+    }
+
+    /// Number of requests forwarded to the wrapped requester,
+    /// ie. the number of round-trips that were actually made.
+    #[trusted]
+    pub fn misses(&self) -> u64 {
+        self.misses.get()
+    }
+
+    /// Drop all cached responses. The counters are left untouched.
+    #[trusted]
+    pub fn clear(&self) {
+        self.signed_headers.borrow_mut().clear();
+        self.validator_sets.borrow_mut().clear();
+    }
+
+    #[trusted]
+    fn record_hit(&self) {
+        self.hits.set(self.hits.get().saturating_add(1));
+    }
+
+    #[trusted]
+    fn record_miss(&self) {
+        self.misses.set(self.misses.get().saturating_add(1));
+    }
+}
+
+impl<C, H, R> Requester<C, H> for CachingRequester<C, H, R>
+where
+    C: Commit,
+    H: Header,
+    R: Requester<C, H>,
+{
+    #[trusted]
+    fn signed_header(&self, h: Height) -> Result<SignedHeader<C, H>, Error> {
+        if let Some(sh) = self.signed_headers.borrow_mut().get(h) {
+            self.record_hit();
+            return Ok(sh);
+        }
+        self.record_miss();
+        let sh = self.inner.signed_header(h)?;
+        self.signed_headers.borrow_mut().insert(h, sh.clone());
+        Ok(sh)
+    }
+
+    #[trusted]
+    fn validator_set(&self, h: Height) -> Result<ValidatorSetImpl, Error> {
+        if let Some(vals) = self.validator_sets.borrow_mut().get(h) {
+            self.record_hit();
+            return Ok(vals);
+        }
+        self.record_miss();
+        let vals = self.inner.validator_set(h)?;
+        self.validator_sets.borrow_mut().insert(h, vals.clone());
+        Ok(vals)
+    }
+
+    /// The latest height keeps moving, so it is never cached.
+    fn latest_height(&self) -> Result<Height, Error> {
+        self.inner.latest_height()
+    }
+}
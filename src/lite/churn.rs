@@ -0,0 +1,277 @@
+//! Estimates of the validator churn of a chain, used by the adaptive
+//! verification mode to pick how far to skip.
+//!
+//! Every skip attempt tells, through the [`OverlapReport`] of the trusted
+//! validators with the validators at the target height, how much of the
+//! trusted voting power left over that many heights. [`ChurnHistory`] keeps
+//! the most recent of these observations, derives a rate of power lost per
+//! height from them, and guesses the farthest target at which enough of the
+//! trusted power should be left to meet the trust threshold.
+
+use lite::types::{Height, OverlapReport, TrustThreshold};
+
+/// Default number of observations kept by a [`ChurnHistory`].
+pub const DEFAULT_CHURN_HISTORY_LEN: usize = 8;
+
+// Fractions of the trusted voting power are expressed in parts per million.
+const PPM: u64 = 1_000_000;
+
+/// ChurnHistory holds the voting power lost over a number of heights observed
+/// on the most recent skip attempts, the oldest one first.
+pub struct ChurnHistory {
+    capacity: usize,
+    // (lost power in parts per million of the trusted voting power, heights)
+    observations: Vec<(u64, u64)>,
+}
+
+impl ChurnHistory {
+    /// An empty history, which keeps at most `capacity` observations.
+    #[trusted]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            observations: Vec::new(),
+        }
+    }
+
+    /// Record the overlap of the validators trusted at `trusted_height`, ie. its next
+    /// validators, with the validators at `untrusted_height`, evicting the oldest
+    /// observation if the capacity is exceeded.
+    ///
+    /// Overlaps of an empty trusted set, at adjacent heights, where the validators
+    /// cannot have changed, or where none of the trusted power is left, which only
+    /// bounds the churn, are ignored.
+    #[trusted]
+    pub fn record(
+        &mut self,
+        overlap: &OverlapReport,
+        trusted_height: Height,
+        untrusted_height: Height,
+    ) {
+        let total = overlap.trusted_total_power();
+        let retained = overlap.retained_power();
+        // the trusted validators are those of trusted_height + 1
+        let distance = untrusted_height.saturating_sub(trusted_height).saturating_sub(1);
+        if total == 0 || retained == 0 || distance == 0 || self.capacity == 0 {
+            return;
+        }
+        let lost = (total - retained.min(total)) as u128 * PPM as u128 / total as u128;
+        if self.observations.len() >= self.capacity {
+            self.observations.remove(0);
+        }
+        self.observations.push((lost as u64, distance));
+    }
+
+    /// The rate of voting power lost per height over the recorded observations,
+    /// in parts per million of the trusted voting power, or None if nothing
+    /// was recorded. Longer observations weigh more, as the validators
+    /// usually change by steps rather than continuously.
+    #[trusted]
+    pub fn rate(&self) -> Option<u64> {
+        let mut lost: u128 = 0;
+        let mut heights: u128 = 0;
+        for &(l, d) in &self.observations {
+            lost += l as u128;
+            heights += d as u128;
+        }
+        if heights == 0 {
+            return None;
+        }
+        Some((lost / heights) as u64)
+    }
+
+    /// The farthest height up to `to` which should be reachable from `from` in
+    /// a single skip: the one at which the trusted voting power left, as
+    /// extrapolated from the recorded rate, still meets the trust threshold.
+    ///
+    /// Without any observation or churn, this is `to`, as with plain bisection.
+    /// If even the next height is not expected to meet the threshold,
+    /// this is the next height, which adjacent verification accepts anyway.
+    #[requires="from < to"]
+    #[ensures="from < result && result <= to"]
+    pub fn farthest_target<L>(&self, from: Height, to: Height, trust_threshold: L) -> Height
+    where
+        L: TrustThreshold,
+    {
+        let rate = match self.rate() {
+            Some(rate) => rate,
+            None => return to,
+        };
+        if rate == 0 {
+            return to;
+        }
+
+        // the power left decreases with the distance: binary search
+        // the largest distance at which it is still enough
+        let mut low = 1;
+        let mut high = to - from;
+        if trust_threshold.is_enough_power(expected_left(rate, high), PPM) {
+            return to;
+        }
+        if !trust_threshold.is_enough_power(expected_left(rate, low), PPM) {
+            return from + 1;
+        }
+        // enough at low, not enough at high
+        while low < high && high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if trust_threshold.is_enough_power(expected_left(rate, mid), PPM) {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        if low >= 1 && low <= to - from {
+            from + low
+        } else {
+            from + 1
+        }
+    }
+}
+
+// The trusted voting power expected to be left, in parts per million, when skipping
+// the given distance at the given rate. The validators trusted at a height are
+// those of the next height: they change over one height less than the distance.
+fn expected_left(rate: u64, distance: u64) -> u64 {
+    if distance == 0 {
+        return PPM;
+    }
+    // in u128, where the product of two u64 cannot overflow
+    let lost = (rate as u128) * ((distance - 1) as u128);
+    if lost >= PPM as u128 {
+        return 0;
+    }
+    PPM - lost as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use lite::clock::MockClock;
+    use lite::error::Error;
+    use lite::memory_provider::MemoryProvider;
+    use lite::options::{OptionsBuilder, VerificationMode};
+    use lite::test_utils::{chain, signed_header, validators, TestCommit, TestHeader};
+    use lite::types::{
+        Header, Height, Requester, SignedHeader, TrustThresholdFraction, TrustedState,
+        ValidatorSetImpl,
+    };
+    use lite::verifier::verify_bisection_with_history;
+
+    use super::{ChurnHistory, DEFAULT_CHURN_HISTORY_LEN};
+
+    // Counts the requests made to the wrapped requester.
+    struct Counting<R> {
+        inner: R,
+        requests: Cell<usize>,
+    }
+
+    impl<R> Requester<TestCommit, TestHeader> for Counting<R>
+    where
+        R: Requester<TestCommit, TestHeader>,
+    {
+        fn signed_header(&self, h: Height) -> Result<SignedHeader<TestCommit, TestHeader>, Error> {
+            self.requests.set(self.requests.get() + 1);
+            self.inner.signed_header(h)
+        }
+        fn validator_set(&self, h: Height) -> Result<ValidatorSetImpl, Error> {
+            self.requests.set(self.requests.get() + 1);
+            self.inner.validator_set(h)
+        }
+        fn latest_height(&self) -> Result<Height, Error> {
+            self.inner.latest_height()
+        }
+    }
+
+    fn counting(length: Height, every: u64) -> Counting<MemoryProvider<TestCommit, TestHeader>> {
+        Counting {
+            inner: chain(length, every),
+            requests: Cell::new(0),
+        }
+    }
+
+    // The number of requests made to verify the chain from height `from` to `to`.
+    fn requests_from(
+        from: Height,
+        to: Height,
+        every: u64,
+        mode: VerificationMode,
+        history: &mut ChurnHistory,
+    ) -> usize {
+        let trusting_period = Duration::from_secs(100_000);
+        let options = OptionsBuilder::new(TrustThresholdFraction::default(), trusting_period)
+            .mode(mode)
+            .build()
+            .unwrap();
+        let clock = MockClock::new(UNIX_EPOCH + Duration::from_secs(to + 1));
+        let req = counting(to, every);
+        let trusted = TrustedState::new(&signed_header(from, every), &validators(from + 1, every));
+        let states =
+            verify_bisection_with_history(trusted, to, &options, &clock, &req, history).unwrap();
+        assert_eq!(states.last().unwrap().last_header().header().height(), to);
+        req.requests.get()
+    }
+
+    fn requests(length: Height, every: u64, mode: VerificationMode) -> usize {
+        let mut history = ChurnHistory::new(DEFAULT_CHURN_HISTORY_LEN);
+        requests_from(1, length, every, mode, &mut history)
+    }
+
+    #[test]
+    fn adaptive_makes_fewer_requests_than_bisection_on_churning_chains() {
+        for &(length, every) in &[(200, 1), (200, 2), (1000, 3), (1000, 10)] {
+            let bisection = requests(length, every, VerificationMode::Skipping);
+            let adaptive = requests(length, every, VerificationMode::Adaptive);
+            assert!(
+                adaptive < bisection,
+                "{} heights, one validator replaced every {}: \
+                 {} requests with adaptive, {} with bisection",
+                length,
+                every,
+                adaptive,
+                bisection,
+            );
+        }
+    }
+
+    #[test]
+    fn adaptive_skips_to_the_target_without_churn() {
+        let bisection = requests(100, 1000, VerificationMode::Skipping);
+        let adaptive = requests(100, 1000, VerificationMode::Adaptive);
+        assert_eq!(adaptive, bisection);
+    }
+
+    #[test]
+    fn history_carries_the_churn_across_syncs() {
+        let mut history = ChurnHistory::new(DEFAULT_CHURN_HISTORY_LEN);
+        requests_from(1, 200, 1, VerificationMode::Adaptive, &mut history);
+        let with_history = requests_from(200, 400, 1, VerificationMode::Adaptive, &mut history);
+
+        let mut fresh = ChurnHistory::new(DEFAULT_CHURN_HISTORY_LEN);
+        let without_history = requests_from(200, 400, 1, VerificationMode::Adaptive, &mut fresh);
+        assert!(with_history < without_history);
+    }
+
+    #[test]
+    fn validators_far_apart_do_not_overlap() {
+        // the numbers of the validators 251 or 256 apart share their lowest byte
+        for &distance in &[10, 251, 256, 1 << 16] {
+            let overlap = validators(0, 1).overlap(&validators(distance, 1));
+            assert_eq!(overlap.retained_validators(), 0);
+        }
+    }
+
+    #[test]
+    fn farthest_target_stays_within_bounds() {
+        let history = ChurnHistory::new(DEFAULT_CHURN_HISTORY_LEN);
+        assert_eq!(history.farthest_target(1, 100, TrustThresholdFraction::default()), 100);
+
+        let trusted = validators(2, 1);
+        let mut history = ChurnHistory::new(DEFAULT_CHURN_HISTORY_LEN);
+        history.record(&trusted.overlap(&validators(5, 1)), 1, 5);
+        let target = history.farthest_target(1, 100, TrustThresholdFraction::default());
+        assert!(1 < target && target < 100);
+        assert_eq!(history.farthest_target(1, 2, TrustThresholdFraction::default()), 2);
+    }
+}
@@ -0,0 +1,69 @@
+//! Clocks the light client reads the current time from.
+
+use std::cell::Cell;
+use std::time::{Duration, SystemTime};
+
+/// Clock tells the current time. It is read every time the verifier
+/// needs to know whether a trusted state has expired, so that a long
+/// running verification notices when it does.
+pub trait Clock {
+    fn now(&self) -> SystemTime;
+}
+
+/// The system's clock.
+#[derive(Copy, Clone, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[trusted]
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock which only moves when told to, eg. to simulate a slow verification.
+pub struct MockClock {
+    now: Cell<SystemTime>,
+    /// Duration the clock moves forward every time it is read.
+    step: Duration,
+}
+
+impl MockClock {
+    /// A clock stopped at the given time.
+    #[trusted]
+    pub fn new(now: SystemTime) -> Self {
+        Self {
+            now: Cell::new(now),
+            step: Duration::from_secs(0),
+        }
+    }
+
+    /// A clock starting at the given time, which moves forward by step
+    /// every time it is read.
+    #[trusted]
+    pub fn with_step(now: SystemTime, step: Duration) -> Self {
+        Self {
+            now: Cell::new(now),
+            step,
+        }
+    }
+
+    #[trusted]
+    pub fn set(&self, now: SystemTime) {
+        self.now.set(now);
+    }
+
+    #[trusted]
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+}
+
+impl Clock for MockClock {
+    #[trusted]
+    fn now(&self) -> SystemTime {
+        let now = self.now.get();
+        self.now.set(now + self.step);
+        now
+    }
+}
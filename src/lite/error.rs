@@ -1,15 +1,108 @@
 //! All error types tied to the light client.
 
+use super::super::error::Error as CryptoError;
 use super::super::hash::Hash;
+use super::types::{Height, Provider};
 
-use std::time::SystemTime;
+use std::error::Error as StdError;
+use std::fmt;
+use std::time::{Duration, SystemTime};
 
 /// The main error type verification methods will return.
-/// See [`Kind`] for the different kind of errors.
-pub type Error = Kind;
+/// It holds the [`Kind`] of error, along with the height which was being
+/// verified and the provider involved, when they are known.
+/// The underlying error which caused it, if any, is its [`StdError::source`],
+/// see [`Kind::context`].
+#[derive(Debug)]
+pub struct Error {
+    kind: Kind,
+    height: Option<Height>,
+    provider: Option<Provider>,
+    source: Option<BoxError>,
+}
+
+/// An underlying error, as wrapped by [`Kind::context`].
+pub type BoxError = Box<dyn StdError + Send + Sync>;
+
+impl Error {
+    pub fn kind(&self) -> &Kind {
+        &self.kind
+    }
+
+    /// The height which was being verified when the error occurred, if known.
+    pub fn height(&self) -> Option<Height> {
+        self.height
+    }
+
+    /// The provider whose data or answer caused the error, if known.
+    pub fn provider(&self) -> Option<Provider> {
+        self.provider
+    }
+
+    /// Record the height which was being verified, unless a more
+    /// specific height was already recorded.
+    pub fn at_height(mut self, height: Height) -> Self {
+        if self.height.is_none() {
+            self.height = Some(height);
+        }
+        self
+    }
+
+    /// Record the provider involved, unless one was already recorded.
+    pub fn from_provider(mut self, provider: Provider) -> Self {
+        if self.provider.is_none() {
+            self.provider = Some(provider);
+        }
+        self
+    }
+}
+
+impl From<Kind> for Error {
+    fn from(kind: Kind) -> Self {
+        Error {
+            kind,
+            height: None,
+            provider: None,
+            source: None,
+        }
+    }
+}
+
+impl From<CryptoError> for Error {
+    fn from(e: CryptoError) -> Self {
+        Kind::Crypto(e).into()
+    }
+}
+
+impl fmt::Display for Error {
+    #[trusted]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(height) = self.height {
+            write!(f, " (verifying height {})", height)?;
+        }
+        if let Some(provider) = self.provider {
+            write!(f, " (provider: {})", provider)?;
+        }
+        Ok(())
+    }
+}
+
+/// The source of an error is the error attached with [`Kind::context`], if any,
+/// or else the crate-level error of [`Kind::Crypto`].
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match (&self.source, &self.kind) {
+            (Some(source), _) => Some(source.as_ref()),
+            (None, Kind::Crypto(e)) => Some(e),
+            (None, _) => None,
+        }
+    }
+}
 
 /// All error kinds related to the light client.
 // #[derive(Clone)]
+#[derive(Debug)]
 pub enum Kind {
     /// The provided header expired.
     Expired { at: SystemTime, now: SystemTime },
@@ -17,30 +110,61 @@ pub enum Kind {
     /// Trusted header is from the future.
     DurationOutOfRange,
 
+    /// The time at which the header expires, its time plus the trusting period,
+    /// cannot be represented.
+    ExpirationOutOfRange {
+        header_time: SystemTime,
+        trusting_period: Duration,
+    },
+
     /// Header height smaller than expected.
     NonIncreasingHeight { got: u64, expected: u64 },
 
     /// Header time is in the past compared to already trusted header.
     NonIncreasingTime,
 
+    /// Header time is too far in the future compared to our clock.
+    HeaderFromFuture { height: u64 },
+
+    /// The latest header of the provider is too old compared to our clock.
+    LaggingProvider { height: u64 },
+
+    /// The header fetched at the subjectively trusted height does not have
+    /// the configured hash.
+    TrustedHashMismatch { height: u64 },
+
     /// Invalid validator hash.
     InvalidValidatorSet {
-        // header_val_hash: Hash,
-        // val_hash: Hash,
+        header_val_hash: Hash,
+        val_hash: Hash,
     },
 
-    /// Invalid next validator hash.
+    /// Invalid next validator hash: the next validator set served along with
+    /// a header does not match the header.
     InvalidNextValidatorSet {
-        // header_next_val_hash: Hash,
-        // next_val_hash: Hash,
+        header_next_val_hash: Hash,
+        next_val_hash: Hash,
+    },
+
+    /// The validators of a header do not match the next validators of the
+    /// trusted header at the height just below, ie. the chain is broken between them.
+    InvalidValidatorSetLink {
+        trusted_height: u64,
+        trusted_next_val_hash: Hash,
+        untrusted_height: u64,
+        untrusted_val_hash: Hash,
     },
 
     /// Commit is not for the header we expected.
     InvalidCommitValue {
-        // header_hash: Hash,
-        // commit_hash: Hash,
+        header_hash: Hash,
+        commit_hash: Hash,
     },
 
+    /// The total voting power of a validator set is above
+    /// [`MAX_TOTAL_VOTING_POWER`](lite::types::MAX_TOTAL_VOTING_POWER).
+    TotalVotingPowerTooLarge { total: u64 },
+
     /// Signed power does not account for +2/3 of total voting power.
     InvalidCommit { total: u64, signed: u64 },
 
@@ -53,6 +177,46 @@ pub enum Kind {
     /// This is returned if an invalid TrustThreshold is created.
     InvalidTrustThreshold,
 
+    /// The trusting period is zero or not shorter than the unbonding period.
+    InvalidTrustingPeriod,
+
+    /// The maximum clock drift is not shorter than the trusting period.
+    InvalidClockDrift,
+
+    /// Different headers were returned for the same height.
+    ConflictingHeaders { height: u64 },
+
+    /// The given traces do not make up evidence of a light client attack.
+    InvalidEvidence,
+
+    /// The primary provider had to be replaced, but no witness was left to replace it,
+    /// or no witness served the header to cross-check.
+    NoWitnesses,
+
+    /// The light client was asked to be reinitialized while its latest
+    /// trusted state has not expired.
+    NotExpired,
+
+    /// The light store holds no state at the given height.
+    NotInStore { height: u64 },
+
+    /// The light store holds no state which can be used to verify the
+    /// given height.
+    NoTrustedState { height: u64 },
+
+    /// Reading from or writing to the light store failed.
+    StoreFailed,
+
+    /// The persisted light store is malformed.
+    CorruptStore,
+
+    /// The persisted light store uses a layout this version cannot read.
+    UnsupportedStoreVersion { version: u32 },
+
+    /// A cryptographic or parsing operation failed, eg. a hash or a signature
+    /// received from a provider is malformed.
+    Crypto(CryptoError),
+
     /// Use the [`Kind::context`] method to wrap the underlying error of
     /// the implementation, if any.
     RequestFailed,
@@ -60,4 +224,154 @@ pub enum Kind {
     /// Use the [`Kind::context`] method to wrap the underlying error of
     /// the implementation, if any.
     ImplementationSpecific,
-}
\ No newline at end of file
+}
+
+impl From<CryptoError> for Kind {
+    fn from(e: CryptoError) -> Self {
+        Kind::Crypto(e)
+    }
+}
+
+impl Kind {
+    /// Make an error of this kind, caused by the given underlying error,
+    /// eg. the IO or decoding error which made a request fail.
+    /// A message is turned into an error: `Kind::ImplementationSpecific.context("overflow")`.
+    pub fn context<E>(self, source: E) -> Error
+    where
+        E: Into<BoxError>,
+    {
+        Error {
+            kind: self,
+            height: None,
+            provider: None,
+            source: Some(source.into()),
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    #[trusted]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Kind::Expired { at, now } => {
+                write!(f, "header expired at {:?}, now is {:?}", at, now)
+            }
+            Kind::DurationOutOfRange => write!(f, "trusted header time is out of range"),
+            Kind::ExpirationOutOfRange {
+                header_time,
+                trusting_period,
+            } => write!(
+                f,
+                "expiration time of header at {:?} with trusting period {:?} is out of range",
+                header_time, trusting_period
+            ),
+            Kind::NonIncreasingHeight { got, expected } => write!(
+                f,
+                "non increasing height: got {}, expected at least {}",
+                got, expected
+            ),
+            Kind::NonIncreasingTime => {
+                write!(f, "header time is not after the trusted header time")
+            }
+            Kind::HeaderFromFuture { height } => {
+                write!(f, "header at height {} is from the future", height)
+            }
+            Kind::LaggingProvider { height } => write!(
+                f,
+                "latest header of the provider, at height {}, is too old",
+                height
+            ),
+            Kind::TrustedHashMismatch { height } => write!(
+                f,
+                "header at trusted height {} does not have the trusted hash",
+                height
+            ),
+            Kind::InvalidValidatorSet {
+                header_val_hash,
+                val_hash,
+            } => write!(
+                f,
+                "header validators hash {} does not match validator set hash {}",
+                header_val_hash, val_hash
+            ),
+            Kind::InvalidNextValidatorSet {
+                header_next_val_hash,
+                next_val_hash,
+            } => write!(
+                f,
+                "header next validators hash {} does not match next validator set hash {}",
+                header_next_val_hash, next_val_hash
+            ),
+            Kind::InvalidValidatorSetLink {
+                trusted_height,
+                trusted_next_val_hash,
+                untrusted_height,
+                untrusted_val_hash,
+            } => write!(
+                f,
+                "next validators hash {} of trusted header at height {} does not match \
+                 validators hash {} of header at height {}",
+                trusted_next_val_hash, trusted_height, untrusted_val_hash, untrusted_height
+            ),
+            Kind::InvalidCommitValue {
+                header_hash,
+                commit_hash,
+            } => write!(
+                f,
+                "header hash {} does not match commit header hash {}",
+                header_hash, commit_hash
+            ),
+            Kind::TotalVotingPowerTooLarge { total } => write!(
+                f,
+                "total voting power {} exceeds the maximum total voting power",
+                total
+            ),
+            Kind::InvalidCommit { total, signed } => write!(
+                f,
+                "invalid commit: signed power {} is not more than 2/3 of total power {}",
+                signed, total
+            ),
+            Kind::InsufficientVotingPower { total, signed } => write!(
+                f,
+                "insufficient voting power: signed power {} out of total power {} \
+                 does not meet the trust threshold",
+                signed, total
+            ),
+            Kind::InvalidTrustThreshold => write!(f, "invalid trust threshold"),
+            Kind::InvalidTrustingPeriod => write!(f, "invalid trusting period"),
+            Kind::InvalidClockDrift => {
+                write!(f, "maximum clock drift is not shorter than the trusting period")
+            }
+            Kind::ConflictingHeaders { height } => {
+                write!(f, "conflicting headers at height {}", height)
+            }
+            Kind::InvalidEvidence => write!(f, "invalid light client attack evidence"),
+            Kind::NoWitnesses => write!(f, "no witness left"),
+            Kind::NotExpired => write!(f, "latest trusted state has not expired"),
+            Kind::NotInStore { height } => write!(f, "no state at height {} in store", height),
+            Kind::NoTrustedState { height } => write!(
+                f,
+                "no trusted state in store to verify height {} from",
+                height
+            ),
+            Kind::StoreFailed => write!(f, "light store operation failed"),
+            Kind::CorruptStore => write!(f, "light store is corrupt"),
+            Kind::UnsupportedStoreVersion { version } => {
+                write!(f, "unsupported light store version {}", version)
+            }
+            Kind::Crypto(e) => write!(f, "cryptographic or parse error: {}", e),
+            Kind::RequestFailed => write!(f, "request failed"),
+            Kind::ImplementationSpecific => write!(f, "implementation specific error"),
+        }
+    }
+}
+
+impl fmt::Display for Provider {
+    #[trusted]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Provider::Primary => write!(f, "primary"),
+            Provider::Witness(i) => write!(f, "witness {}", i),
+        }
+    }
+}
@@ -0,0 +1,320 @@
+//! Evidence of light client attacks, to be submitted to full nodes
+//! once a conflicting header has been found.
+
+use std::time::SystemTime;
+
+use lite::error::{Error, Kind};
+use lite::proto::{encode_timestamp, put_bytes_field, put_u64_field, EncodeProto};
+use lite::types::{Commit, Header, Height, SignedHeader, TrustedState, Validator, ValidatorSetImpl};
+
+/// LightClientAttackEvidence is the evidence that some validators signed a
+/// header conflicting with the one the light client verified at the same height.
+pub struct LightClientAttackEvidence<C, H>
+where
+    C: Commit,
+    H: Header,
+{
+    /// The conflicting header, together with the validator set which signed it.
+    conflicting_header: SignedHeader<C, H>,
+    conflicting_validators: ValidatorSetImpl,
+    /// Height of the last header both traces agree on.
+    common_height: Height,
+    /// The validators which signed both conflicting commits.
+    byzantine_validators: Vec<Validator>,
+    /// Total voting power of the validator set trusted at the common height.
+    total_voting_power: u64,
+    /// Time of the header at the common height.
+    timestamp: SystemTime,
+}
+
+impl<C, H> LightClientAttackEvidence<C, H>
+where
+    C: Commit,
+    H: Header,
+{
+    /// Build the evidence from two divergent traces. Both traces start from
+    /// a common trusted state and end with conflicting states at the same height,
+    /// eg. the primary trace and the trace of a witness reported by
+    /// [`super::fork_detector::detect_forks`].
+    /// The conflicting validators are the validator set, at the conflicting
+    /// height, which signed the last header of the conflicting trace.
+    ///
+    /// The common height is the highest height at which both traces hold
+    /// the same header, see [`common_height`]. The common validators are the
+    /// validator set at that height, which gives the total voting power of the evidence.
+    ///
+    /// Returns [`Kind::InvalidEvidence`] if the traces do not end at the same
+    /// height with different headers or if they have no header in common, and
+    /// [`Kind::InvalidValidatorSet`] if the conflicting validators did not sign
+    /// the conflicting header or the common validators are not the ones of the common header.
+    #[trusted]
+    pub fn from_traces(
+        trusted_trace: &[TrustedState<C, H>],
+        conflicting_trace: &[TrustedState<C, H>],
+        conflicting_validators: &ValidatorSetImpl,
+        common_validators: &ValidatorSetImpl,
+    ) -> Result<Self, Error> {
+        let (trusted, conflicting) = match (trusted_trace.last(), conflicting_trace.last()) {
+            (Some(trusted), Some(conflicting)) => (trusted.last_header(), conflicting.last_header()),
+            _ => return Err(Kind::InvalidEvidence.into()),
+        };
+        if trusted.header().height() != conflicting.header().height()
+            || trusted.header().hash() == conflicting.header().hash()
+        {
+            return Err(Kind::InvalidEvidence.into());
+        }
+        if conflicting.header().validators_hash() != conflicting_validators.hash() {
+            return Err(Kind::InvalidValidatorSet {
+                header_val_hash: conflicting.header().validators_hash(),
+                val_hash: conflicting_validators.hash(),
+            }.into());
+        }
+
+        let common_height = match common_height(trusted_trace, conflicting_trace) {
+            Some(common_height) => common_height,
+            None => return Err(Kind::InvalidEvidence.into()),
+        };
+        let common = trusted_trace
+            .iter()
+            .map(|state| state.last_header().header())
+            .find(|header| header.height() == common_height);
+        let common = match common {
+            Some(header) => header,
+            None => return Err(Kind::InvalidEvidence.into()),
+        };
+        // the trusted states hold the next validators of their header:
+        // the total voting power is the one of the validators of the common header
+        if common.validators_hash() != common_validators.hash() {
+            return Err(Kind::InvalidValidatorSet {
+                header_val_hash: common.validators_hash(),
+                val_hash: common_validators.hash(),
+            }.into());
+        }
+        let total_voting_power = common_validators.total_power();
+
+        let trusted_signers = trusted.commit().signers();
+        let conflicting_signers = conflicting.commit().signers();
+        let byzantine_validators = conflicting_validators
+            .validators()
+            .iter()
+            .filter(|v| {
+                trusted_signers.contains(&v.address()) && conflicting_signers.contains(&v.address())
+            })
+            .cloned()
+            .collect();
+
+        Ok(Self {
+            conflicting_header: conflicting.clone(),
+            conflicting_validators: conflicting_validators.clone(),
+            common_height: common.height(),
+            byzantine_validators,
+            total_voting_power,
+            timestamp: common.bft_time(),
+        })
+    }
+
+    pub fn conflicting_header(&self) -> &SignedHeader<C, H> {
+        &self.conflicting_header
+    }
+
+    pub fn conflicting_validators(&self) -> &ValidatorSetImpl {
+        &self.conflicting_validators
+    }
+
+    pub fn common_height(&self) -> Height {
+        self.common_height
+    }
+
+    pub fn byzantine_validators(&self) -> &[Validator] {
+        &self.byzantine_validators
+    }
+
+    pub fn total_voting_power(&self) -> u64 {
+        self.total_voting_power
+    }
+
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+}
+
+impl<C, H> LightClientAttackEvidence<C, H>
+where
+    C: Commit + EncodeProto,
+    H: Header + EncodeProto,
+{
+    /// Encode the evidence as a `tendermint.types.Evidence`, ie. the message
+    /// expected by full nodes.
+    pub fn encode_evidence_proto(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        put_bytes_field(&mut buf, 2, &self.encode_proto());
+        buf
+    }
+}
+
+impl<C, H> EncodeProto for LightClientAttackEvidence<C, H>
+where
+    C: Commit + EncodeProto,
+    H: Header + EncodeProto,
+{
+    /// `tendermint.types.LightClientAttackEvidence`.
+    #[trusted]
+    fn encode_proto(&self) -> Vec<u8> {
+        let mut signed_header = Vec::new();
+        put_bytes_field(&mut signed_header, 1, &self.conflicting_header.header().encode_proto());
+        put_bytes_field(&mut signed_header, 2, &self.conflicting_header.commit().encode_proto());
+
+        let mut light_block = Vec::new();
+        put_bytes_field(&mut light_block, 1, &signed_header);
+        put_bytes_field(&mut light_block, 2, &self.conflicting_validators.encode_proto());
+
+        let mut buf = Vec::new();
+        put_bytes_field(&mut buf, 1, &light_block);
+        put_u64_field(&mut buf, 2, self.common_height);
+        for validator in &self.byzantine_validators {
+            put_bytes_field(&mut buf, 3, &validator.encode_proto());
+        }
+        put_u64_field(&mut buf, 4, self.total_voting_power);
+        put_bytes_field(&mut buf, 5, &encode_timestamp(self.timestamp));
+        buf
+    }
+}
+
+/// The highest height at which both traces hold the same header, if any.
+#[trusted]
+pub fn common_height<C, H>(
+    trace: &[TrustedState<C, H>],
+    other_trace: &[TrustedState<C, H>],
+) -> Option<Height>
+where
+    C: Commit,
+    H: Header,
+{
+    other_trace
+        .iter()
+        .rev()
+        .map(|state| state.last_header().header())
+        .find(|header| {
+            trace.iter().any(|state| {
+                let candidate = state.last_header().header();
+                candidate.height() == header.height() && candidate.hash() == header.hash()
+            })
+        })
+        .map(|header| header.height())
+}
+
+/// The kinds of light client attacks.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum AttackKind {
+    /// The conflicting header could not have been derived from the state
+    /// at the previous height: one of the hashes it derives from that state,
+    /// see [`derives_from_same_state`], differs from the trusted header's.
+    Lunatic,
+    /// The same validators signed both headers in the same round.
+    Equivocation,
+    /// The same validators signed both headers, in different rounds.
+    Amnesia,
+}
+
+/// AttackAnalysis tells which kind of attack happened and which validators misbehaved.
+pub struct AttackAnalysis {
+    kind: AttackKind,
+    misbehaving: Vec<Validator>,
+}
+
+impl AttackAnalysis {
+    pub fn kind(&self) -> AttackKind {
+        self.kind
+    }
+
+    /// The misbehaving validators, with their voting power.
+    /// For amnesia attacks, these are the validators which signed both headers:
+    /// which of them actually misbehaved cannot be told from the headers alone.
+    pub fn misbehaving(&self) -> &[Validator] {
+        &self.misbehaving
+    }
+
+    /// Total voting power of the misbehaving validators.
+    #[trusted]
+    pub fn misbehaving_power(&self) -> u64 {
+        self.misbehaving
+            .iter()
+            .fold(0u64, |total, v| total.saturating_add(v.power()))
+    }
+}
+
+/// Decide which kind of attack produced the conflicting header, given the
+/// trusted header at the same height.
+///
+/// The trusted validators are the validator set which signed the trusted header, and
+/// the common validators are the ones of the last trusted state both traces agree on,
+/// ie. the validators from which the conflicting header was verified.
+///
+/// - For lunatic attacks, the misbehaving validators are the common validators which
+///   signed the conflicting header.
+/// - For equivocation and amnesia attacks, they are the trusted validators which
+///   signed both headers.
+///
+/// Returns [`Kind::InvalidEvidence`] if the headers are not at the same height or are equal.
+#[trusted]
+pub fn analyze_attack<C, H>(
+    trusted: &SignedHeader<C, H>,
+    trusted_validators: &ValidatorSetImpl,
+    conflicting: &SignedHeader<C, H>,
+    common_validators: &ValidatorSetImpl,
+) -> Result<AttackAnalysis, Error>
+where
+    C: Commit,
+    H: Header,
+{
+    if trusted.header().height() != conflicting.header().height()
+        || trusted.header().hash() == conflicting.header().hash()
+    {
+        return Err(Kind::InvalidEvidence.into());
+    }
+
+    let conflicting_signers = conflicting.commit().signers();
+    if !derives_from_same_state(trusted.header(), conflicting.header()) {
+        let misbehaving = common_validators
+            .validators()
+            .iter()
+            .filter(|v| conflicting_signers.contains(&v.address()))
+            .cloned()
+            .collect();
+        return Ok(AttackAnalysis {
+            kind: AttackKind::Lunatic,
+            misbehaving,
+        });
+    }
+
+    let kind = if trusted.commit().round() == conflicting.commit().round() {
+        AttackKind::Equivocation
+    } else {
+        AttackKind::Amnesia
+    };
+    let trusted_signers = trusted.commit().signers();
+    let misbehaving = trusted_validators
+        .validators()
+        .iter()
+        .filter(|v| {
+            trusted_signers.contains(&v.address()) && conflicting_signers.contains(&v.address())
+        })
+        .cloned()
+        .collect();
+    Ok(AttackAnalysis { kind, misbehaving })
+}
+
+/// Whether two headers at the same height hold the same hashes derived from
+/// the state of the application at the previous height: validators, next
+/// validators, consensus parameters, application state and last results.
+/// If not, at most one of them was produced by the chain.
+pub fn derives_from_same_state<H>(header: &H, other: &H) -> bool
+where
+    H: Header,
+{
+    header.validators_hash() == other.validators_hash()
+        && header.next_validators_hash() == other.next_validators_hash()
+        && header.consensus_hash() == other.consensus_hash()
+        && header.app_hash() == other.app_hash()
+        && header.last_results_hash() == other.last_results_hash()
+}
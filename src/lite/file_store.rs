@@ -0,0 +1,563 @@
+//! A [`LightStore`] persisted to disk, so that a light node can resume
+//! from its trusted states after a restart instead of syncing from genesis.
+//!
+//! The store is an append-only log of checksummed records:
+//!
+//! ```text
+//! header: magic "TLSS" | version: u32
+//! record: op: u8 | height: u64 | status: u8 | len: u32 | payload: [u8; len] | crc32: u32
+//! ```
+//!
+//! All integers are little-endian and the checksum covers every preceding
+//! field of the record. Appending a record is followed by a sync, so a crash
+//! can at worst leave a partially written record at the end of the log.
+//! Such a corrupt tail is detected on load and dropped.
+//! When an append fails, the log is truncated back to its last record
+//! so that later records are not written after a partial one. If even
+//! that fails, the store refuses any further write until it is compacted.
+//! Whenever the log is rewritten as a whole (on creation, after dropping a
+//! corrupt tail, and on compaction) the new log is written to a temporary
+//! file which is then renamed over the old one, so a crash leaves either
+//! the old or the new log in place.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use lite::error::{Error, Kind};
+use lite::store::{LightStore, MemoryStore, Status};
+use lite::types::{Commit, Header, Height, TrustedState};
+
+/// Magic bytes at the beginning of a light store log.
+const MAGIC: [u8; 4] = *b"TLSS";
+
+/// Version of the log layout written by this implementation.
+pub const VERSION: u32 = 1;
+
+/// Size of the magic bytes and the version.
+const HEADER_SIZE: usize = 8;
+
+/// Size of the fields of a record preceding the payload.
+const RECORD_PREFIX_SIZE: usize = 14;
+
+/// Size of the checksum following the payload.
+const CHECKSUM_SIZE: usize = 4;
+
+const OP_INSERT: u8 = 1;
+const OP_UPDATE_STATUS: u8 = 2;
+const OP_REMOVE: u8 = 3;
+
+/// StateCodec converts [`TrustedState`]s to and from bytes.
+/// As [`Header`] and [`Commit`] are left to the implementation,
+/// so is their encoding.
+pub trait StateCodec<C, H>
+where
+    C: Commit,
+    H: Header,
+{
+    fn encode(&self, state: &TrustedState<C, H>) -> Vec<u8>;
+
+    /// Decode a state previously encoded with [`StateCodec::encode`].
+    fn decode(&self, bytes: &[u8]) -> Result<TrustedState<C, H>, Error>;
+}
+
+/// LightStore implementation backed by a file.
+/// All light blocks are also kept in memory to answer queries.
+pub struct FileStore<C, H, E>
+where
+    C: Commit,
+    H: Header,
+    E: StateCodec<C, H>,
+{
+    path: PathBuf,
+    log: File,
+    codec: E,
+    blocks: MemoryStore<C, H>,
+    /// Number of records in the log, including the ones made obsolete
+    /// by later records.
+    records: usize,
+    /// Number of bytes dropped from the end of the log when it was loaded.
+    discarded_tail: u64,
+    /// Length of the log, up to the end of its last record.
+    len: u64,
+    /// Whether a failed append could not be rolled back, leaving the log
+    /// with a partial record at its end.
+    poisoned: bool,
+}
+
+impl<C, H, E> FileStore<C, H, E>
+where
+    C: Commit,
+    H: Header,
+    E: StateCodec<C, H>,
+{
+    /// Open the light store persisted at the given path, creating an
+    /// empty one if the file does not exist.
+    ///
+    /// A corrupt tail, ie. a truncated record or a record whose checksum
+    /// does not match, is dropped together with everything following it
+    /// (see [`FileStore::discarded_tail`]).
+    /// Returns [`Kind::UnsupportedStoreVersion`] if the log was written with another
+    /// layout version and [`Kind::CorruptStore`] if its header is malformed.
+    #[trusted]
+    pub fn open<P: AsRef<Path>>(path: P, codec: E) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        if !path.exists() {
+            write_atomically(&path, &encode_header())?;
+        }
+
+        let mut bytes = Vec::new();
+        File::open(&path)
+            .and_then(|mut f| f.read_to_end(&mut bytes))
+            .map_err(|e| Kind::StoreFailed.context(e))?;
+        check_header(&bytes)?;
+
+        let mut blocks = MemoryStore::new();
+        let mut records = 0;
+        let mut offset = HEADER_SIZE;
+        while let Some((record, size)) = decode_record(&bytes[offset..]) {
+            apply(&mut blocks, &codec, record)?;
+            records += 1;
+            offset += size;
+        }
+
+        let discarded_tail = (bytes.len() - offset) as u64;
+        if discarded_tail > 0 {
+            write_atomically(&path, &bytes[..offset])?;
+        }
+
+        let log = open_for_append(&path)?;
+        Ok(Self {
+            path,
+            log,
+            codec,
+            blocks,
+            records,
+            discarded_tail,
+            len: offset as u64,
+            poisoned: false,
+        })
+    }
+
+    /// Number of bytes of corrupt tail dropped when the store was opened.
+    pub fn discarded_tail(&self) -> u64 {
+        self.discarded_tail
+    }
+
+    /// Number of records in the log. Once it grows much larger than the
+    /// number of stored light blocks, the log should be compacted.
+    pub fn records(&self) -> usize {
+        self.records
+    }
+
+    /// Rewrite the log with a single record per stored light block,
+    /// dropping all the records made obsolete by later ones.
+    /// This also drops the partial record a failed write may have left.
+    #[trusted]
+    pub fn compact(&mut self) -> Result<(), Error> {
+        let mut bytes = encode_header();
+        let entries = self.blocks.entries();
+        for (state, status) in &entries {
+            let h = state.last_header().header().height();
+            let payload = self.codec.encode(state);
+            bytes.extend_from_slice(&encode_record(OP_INSERT, h, *status, &payload));
+        }
+        write_atomically(&self.path, &bytes)?;
+        self.log = open_for_append(&self.path)?;
+        self.records = entries.len();
+        self.len = bytes.len() as u64;
+        self.poisoned = false;
+        Ok(())
+    }
+
+    // Append a record to the log. Returns StoreFailed if the write fails,
+    // or if the log could not be rolled back after an earlier failure.
+    #[trusted]
+    fn append(&mut self, op: u8, h: Height, status: Status, payload: &[u8]) -> Result<(), Error> {
+        if self.poisoned {
+            return Err(Kind::StoreFailed.context("the log ends with a partial record"));
+        }
+        let record = encode_record(op, h, status, payload);
+        let written = self.log.write_all(&record).and_then(|_| self.log.sync_data());
+        if let Err(e) = written {
+            // drop whatever part of the record was written
+            let len = self.len;
+            if self.log.set_len(len).and_then(|_| self.log.sync_data()).is_err() {
+                self.poisoned = true;
+            }
+            return Err(Kind::StoreFailed.context(e));
+        }
+        self.records += 1;
+        self.len += record.len() as u64;
+        Ok(())
+    }
+}
+
+impl<C, H, E> LightStore<C, H> for FileStore<C, H, E>
+where
+    C: Commit,
+    H: Header,
+    E: StateCodec<C, H>,
+{
+    fn get(&self, h: Height) -> Option<(TrustedState<C, H>, Status)> {
+        self.blocks.get(h)
+    }
+
+    fn insert(&mut self, state: TrustedState<C, H>, status: Status) -> Result<(), Error> {
+        let h = state.last_header().header().height();
+        let payload = self.codec.encode(&state);
+        self.append(OP_INSERT, h, status, &payload)?;
+        self.blocks.insert(state, status)
+    }
+
+    fn update_status(&mut self, h: Height, status: Status) -> Result<(), Error> {
+        if self.blocks.get(h).is_none() {
+            return Err(Kind::NotInStore { height: h }.into());
+        }
+        self.append(OP_UPDATE_STATUS, h, status, &[])?;
+        self.blocks.update_status(h, status)
+    }
+
+    fn remove(&mut self, h: Height) -> Result<Option<(TrustedState<C, H>, Status)>, Error> {
+        if self.blocks.get(h).is_none() {
+            return Ok(None);
+        }
+        self.append(OP_REMOVE, h, Status::Unverified, &[])?;
+        self.blocks.remove(h)
+    }
+
+    fn latest_trusted(&self) -> Option<TrustedState<C, H>> {
+        self.blocks.latest_trusted()
+    }
+
+    fn highest_below(&self, h: Height) -> Option<TrustedState<C, H>> {
+        self.blocks.highest_below(h)
+    }
+}
+
+/// A record decoded from the log.
+struct Record<'a> {
+    op: u8,
+    height: Height,
+    status: Status,
+    payload: &'a [u8],
+}
+
+/// Replay the given record on the in-memory blocks.
+#[trusted]
+fn apply<C, H, E>(blocks: &mut MemoryStore<C, H>, codec: &E, record: Record) -> Result<(), Error>
+where
+    C: Commit,
+    H: Header,
+    E: StateCodec<C, H>,
+{
+    match record.op {
+        OP_INSERT => {
+            let state = codec.decode(record.payload)?;
+            if state.last_header().header().height() != record.height {
+                return Err(Kind::CorruptStore.context("state stored under another height"));
+            }
+            blocks.insert(state, record.status)
+        }
+        OP_UPDATE_STATUS => blocks.update_status(record.height, record.status),
+        OP_REMOVE => blocks.remove(record.height).map(|_| ()),
+        _ => Err(Kind::CorruptStore.context(format!("unknown record operation {}", record.op))),
+    }
+}
+
+#[trusted]
+fn encode_header() -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_SIZE);
+    bytes.extend_from_slice(&MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes
+}
+
+#[trusted]
+fn check_header(bytes: &[u8]) -> Result<(), Error> {
+    if bytes.len() < HEADER_SIZE || bytes[..4] != MAGIC {
+        return Err(Kind::CorruptStore.context("missing log header"));
+    }
+    let version = read_u32(&bytes[4..8]);
+    if version != VERSION {
+        return Err(Kind::UnsupportedStoreVersion { version }.into());
+    }
+    Ok(())
+}
+
+#[trusted]
+fn encode_record(op: u8, h: Height, status: Status, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(RECORD_PREFIX_SIZE + payload.len() + CHECKSUM_SIZE);
+    bytes.push(op);
+    bytes.extend_from_slice(&h.to_le_bytes());
+    bytes.push(encode_status(status));
+    bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(payload);
+    let checksum = crc32(&bytes);
+    bytes.extend_from_slice(&checksum.to_le_bytes());
+    bytes
+}
+
+/// Decode the record at the beginning of the given bytes, returning it
+/// together with its size. Returns None if the bytes do not start with
+/// a complete record with a valid checksum.
+#[trusted]
+fn decode_record(bytes: &[u8]) -> Option<(Record, usize)> {
+    if bytes.len() < RECORD_PREFIX_SIZE {
+        return None;
+    }
+    let len = read_u32(&bytes[10..14]) as usize;
+    let size = RECORD_PREFIX_SIZE
+        .checked_add(len)?
+        .checked_add(CHECKSUM_SIZE)?;
+    if bytes.len() < size {
+        return None;
+    }
+    let checksum_at = size - CHECKSUM_SIZE;
+    if crc32(&bytes[..checksum_at]) != read_u32(&bytes[checksum_at..size]) {
+        return None;
+    }
+    let status = decode_status(bytes[9])?;
+    let mut height = [0u8; 8];
+    height.copy_from_slice(&bytes[1..9]);
+    let record = Record {
+        op: bytes[0],
+        height: Height::from_le_bytes(height),
+        status,
+        payload: &bytes[RECORD_PREFIX_SIZE..checksum_at],
+    };
+    Some((record, size))
+}
+
+#[pure]
+fn encode_status(status: Status) -> u8 {
+    match status {
+        Status::Unverified => 0,
+        Status::Verified => 1,
+        Status::Trusted => 2,
+        Status::Failed => 3,
+        Status::Archived => 4,
+    }
+}
+
+fn decode_status(byte: u8) -> Option<Status> {
+    match byte {
+        0 => Some(Status::Unverified),
+        1 => Some(Status::Verified),
+        2 => Some(Status::Trusted),
+        3 => Some(Status::Failed),
+        4 => Some(Status::Archived),
+        _ => None,
+    }
+}
+
+#[trusted]
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(bytes);
+    u32::from_le_bytes(buf)
+}
+
+/// CRC-32 (IEEE 802.3) checksum of the given bytes.
+#[trusted]
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[trusted]
+fn open_for_append(path: &Path) -> Result<File, Error> {
+    OpenOptions::new()
+        .append(true)
+        .open(path)
+        .map_err(|e| Kind::StoreFailed.context(e))
+}
+
+/// Replace the content of the file at the given path by the given bytes:
+/// they are written and synced to a temporary file which is then
+/// renamed over the file.
+#[trusted]
+fn write_atomically(path: &Path, bytes: &[u8]) -> Result<(), Error> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let mut tmp = File::create(&tmp_path).map_err(|e| Kind::StoreFailed.context(e))?;
+    tmp.write_all(bytes)
+        .and_then(|_| tmp.sync_all())
+        .map_err(|e| Kind::StoreFailed.context(e))?;
+    fs::rename(&tmp_path, path).map_err(|e| Kind::StoreFailed.context(e))?;
+
+    // make the rename itself durable
+    if let Some(dir) = path.parent() {
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, OpenOptions};
+    use std::path::PathBuf;
+    use std::process;
+
+    use lite::error::{Error, Kind};
+    use lite::store::{LightStore, Status};
+    use lite::test_utils::{signed_header, validators, TestCommit, TestHeader};
+    use lite::types::{Header, Height, TrustedState};
+
+    use super::{FileStore, StateCodec, HEADER_SIZE, MAGIC, VERSION};
+
+    const EVERY: u64 = 3;
+
+    // Encodes the height of the state, the rest of it is rebuilt from the test chain.
+    struct HeightCodec;
+
+    impl StateCodec<TestCommit, TestHeader> for HeightCodec {
+        fn encode(&self, state: &TrustedState<TestCommit, TestHeader>) -> Vec<u8> {
+            state.last_header().header().height().to_le_bytes().to_vec()
+        }
+
+        fn decode(&self, bytes: &[u8]) -> Result<TrustedState<TestCommit, TestHeader>, Error> {
+            if bytes.len() != 8 {
+                return Err(Kind::CorruptStore.context("bad state length"));
+            }
+            let mut height = [0u8; 8];
+            height.copy_from_slice(bytes);
+            Ok(state(Height::from_le_bytes(height)))
+        }
+    }
+
+    type TestStore = FileStore<TestCommit, TestHeader, HeightCodec>;
+
+    fn state(h: Height) -> TrustedState<TestCommit, TestHeader> {
+        TrustedState::new(&signed_header(h, EVERY), &validators(h + 1, EVERY))
+    }
+
+    // A path in a fresh temporary directory.
+    fn store_path(test: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("file-store-{}-{}", test, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("light-store")
+    }
+
+    fn open(path: &PathBuf) -> TestStore {
+        FileStore::open(path, HeightCodec).unwrap()
+    }
+
+    fn status(store: &TestStore, h: Height) -> Option<Status> {
+        store.get(h).map(|(_, status)| status)
+    }
+
+    #[test]
+    fn reopening_replays_the_log() {
+        let path = store_path("reopen");
+        {
+            let mut store = open(&path);
+            store.insert(state(1), Status::Trusted).unwrap();
+            store.insert(state(2), Status::Verified).unwrap();
+            store.insert(state(3), Status::Verified).unwrap();
+            store.update_status(2, Status::Failed).unwrap();
+            store.remove(3).unwrap();
+        }
+
+        let store = open(&path);
+        assert_eq!(store.records(), 5);
+        assert_eq!(store.discarded_tail(), 0);
+        assert!(status(&store, 1) == Some(Status::Trusted));
+        assert!(status(&store, 2) == Some(Status::Failed));
+        assert!(status(&store, 3).is_none());
+        assert_eq!(store.latest_trusted().unwrap().last_header().header().height(), 1);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn a_corrupt_tail_is_dropped() {
+        let path = store_path("corrupt-tail");
+        {
+            let mut store = open(&path);
+            store.insert(state(1), Status::Trusted).unwrap();
+            store.insert(state(2), Status::Verified).unwrap();
+        }
+        // tear the last record
+        let len = fs::metadata(&path).unwrap().len();
+        let log = OpenOptions::new().write(true).open(&path).unwrap();
+        log.set_len(len - 3).unwrap();
+        drop(log);
+
+        let mut store = open(&path);
+        assert_eq!(store.records(), 1);
+        assert!(store.discarded_tail() > 0);
+        assert!(status(&store, 1) == Some(Status::Trusted));
+        assert!(status(&store, 2).is_none());
+        store.insert(state(3), Status::Verified).unwrap();
+        drop(store);
+
+        // the dropped tail is gone for good: later records follow the last good one
+        let store = open(&path);
+        assert_eq!(store.records(), 2);
+        assert_eq!(store.discarded_tail(), 0);
+        assert!(status(&store, 3) == Some(Status::Verified));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn other_versions_and_malformed_headers_are_rejected() {
+        let path = store_path("version");
+        let mut header = MAGIC.to_vec();
+        header.extend_from_slice(&(VERSION + 1).to_le_bytes());
+        fs::write(&path, &header).unwrap();
+        match FileStore::open(&path, HeightCodec) {
+            Err(e) => match e.kind() {
+                Kind::UnsupportedStoreVersion { version } => assert_eq!(*version, VERSION + 1),
+                kind => panic!("unexpected error {:?}", kind),
+            },
+            Ok(_) => panic!("opened a log of another version"),
+        }
+
+        fs::write(&path, &header[..HEADER_SIZE - 1]).unwrap();
+        match FileStore::open(&path, HeightCodec) {
+            Err(e) => match e.kind() {
+                Kind::CorruptStore => (),
+                kind => panic!("unexpected error {:?}", kind),
+            },
+            Ok(_) => panic!("opened a log without header"),
+        }
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn compaction_keeps_a_record_per_light_block() {
+        let path = store_path("compact");
+        let mut store = open(&path);
+        store.insert(state(1), Status::Verified).unwrap();
+        store.update_status(1, Status::Trusted).unwrap();
+        store.insert(state(2), Status::Verified).unwrap();
+        store.insert(state(3), Status::Verified).unwrap();
+        store.remove(2).unwrap();
+        assert_eq!(store.records(), 5);
+
+        store.compact().unwrap();
+        assert_eq!(store.records(), 2);
+        store.update_status(3, Status::Failed).unwrap();
+        drop(store);
+
+        let store = open(&path);
+        assert_eq!(store.records(), 3);
+        assert!(status(&store, 1) == Some(Status::Trusted));
+        assert!(status(&store, 2).is_none());
+        assert!(status(&store, 3) == Some(Status::Failed));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
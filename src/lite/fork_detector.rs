@@ -0,0 +1,448 @@
+//! Detection of forks by cross-checking the headers verified with the
+//! primary provider against witness providers.
+//!
+//! A light client following a single provider cannot notice that it is
+//! being served a fork of the chain. Once a header has been verified with
+//! the primary, each witness is asked for the header at the same height.
+//! Whenever a witness returns a different header, the witness' own trace
+//! from the common trusted state is verified in order to tell a faulty
+//! witness from a faulty primary or from a real light client attack.
+
+use hash::Hash;
+use lite::clock::Clock;
+use lite::error::{Error, Kind};
+use lite::evidence::{common_height, LightClientAttackEvidence};
+use lite::options::Options;
+use lite::predicates::VerificationPredicates;
+use lite::types::{
+    Commit, EvidenceReporter, Header, Height, Provider, Requester, SignedHeader,
+    TrustThreshold, TrustedState,
+};
+use lite::verifier::verify_bisection;
+
+/// The reason why a witness diverged from the primary.
+pub enum Divergence<C, H>
+where
+    C: Commit,
+    H: Header,
+{
+    /// The header of the witness could not be verified from the common trusted state.
+    FaultyWitness { error: Error },
+
+    /// The header of the witness verifies, but the primary no longer serves
+    /// the header we verified with it.
+    FaultyPrimary { error: Error },
+
+    /// Both headers verify from the common trusted state: enough of the trusted
+    /// validators signed two conflicting headers at the same height.
+    /// Holds the witness' trace, starting with the common trusted state and
+    /// ending with the state at the conflicting height.
+    Attack { witness_trace: Vec<TrustedState<C, H>> },
+}
+
+/// Result of the cross-check against a single witness.
+pub enum WitnessOutcome<C, H>
+where
+    C: Commit,
+    H: Header,
+{
+    /// The witness returned the same header as the primary.
+    Agrees,
+
+    /// The witness could not be asked for its header.
+    Unavailable { error: Error },
+
+    /// The witness returned a different header.
+    Diverged {
+        witness_header: SignedHeader<C, H>,
+        divergence: Divergence<C, H>,
+    },
+}
+
+impl<C, H> WitnessOutcome<C, H>
+where
+    C: Commit,
+    H: Header,
+{
+    #[pure]
+    pub fn is_attack(&self) -> bool {
+        match self {
+            WitnessOutcome::Diverged {
+                divergence: Divergence::Attack { .. },
+                ..
+            } => true,
+            _ => false,
+        }
+    }
+}
+
+/// ForkReport holds the outcome of the cross-check against each witness,
+/// in the order the witnesses were given.
+pub struct ForkReport<C, H>
+where
+    C: Commit,
+    H: Header,
+{
+    height: Height,
+    outcomes: Vec<WitnessOutcome<C, H>>,
+}
+
+impl<C, H> ForkReport<C, H>
+where
+    C: Commit,
+    H: Header,
+{
+    /// Height of the header which was cross-checked.
+    pub fn height(&self) -> Height {
+        self.height
+    }
+
+    pub fn outcomes(&self) -> &[WitnessOutcome<C, H>] {
+        &self.outcomes
+    }
+
+    /// Whether any witness returned a header different from the primary's.
+    #[trusted]
+    pub fn has_divergence(&self) -> bool {
+        self.outcomes.iter().any(|outcome| match outcome {
+            WitnessOutcome::Diverged { .. } => true,
+            _ => false,
+        })
+    }
+
+    /// Whether any witness revealed a light client attack.
+    #[trusted]
+    pub fn has_attack(&self) -> bool {
+        self.outcomes.iter().any(|outcome| outcome.is_attack())
+    }
+}
+
+/// Cross-check the last state of the given primary trace against each witness.
+///
+/// The primary trace starts with the trusted state the primary's header was
+/// verified from and ends with the newly verified state, eg. the trusted state
+/// passed to [`verify_bisection`] followed by the states it returned.
+/// Each witness diverging from the primary is verified from the first state of
+/// the trace, using the witness to fetch the intermediate data.
+///
+/// Returns an error only if the primary trace holds less than two states.
+pub fn detect_forks<C, H, L, V, R, W, K>(
+    primary_trace: &[TrustedState<C, H>],
+    primary: &R,
+    witnesses: &[W],
+    options: &Options<L, V>,
+    clock: &K,
+) -> Result<ForkReport<C, H>, Error>
+    where
+        H: Header,
+        C: Commit,
+        L: TrustThreshold,
+        V: VerificationPredicates,
+        R: Requester<C, H>,
+        W: Requester<C, H>,
+        K: Clock,
+{
+    if primary_trace.len() < 2 {
+        return Err(Kind::ImplementationSpecific.context("primary trace holds less than two states"));
+    }
+    let common = &primary_trace[0];
+    let verified = &primary_trace[primary_trace.len() - 1];
+    let verified_header = verified.last_header().header();
+    let height = verified_header.height();
+
+    let mut outcomes = Vec::with_capacity(witnesses.len());
+    let mut i = 0;
+    while i < witnesses.len() {
+        let witness = &witnesses[i];
+        let outcome = match witness.signed_header(height) {
+            Err(error) => WitnessOutcome::Unavailable {
+                error: error.from_provider(Provider::Witness(i)),
+            },
+            Ok(witness_header) => {
+                if witness_header.header().hash() == verified_header.hash() {
+                    WitnessOutcome::Agrees
+                } else {
+                    let divergence = examine_divergence(
+                        common,
+                        verified,
+                        primary,
+                        witness,
+                        i,
+                        options,
+                        clock,
+                    );
+                    WitnessOutcome::Diverged {
+                        witness_header,
+                        divergence,
+                    }
+                }
+            }
+        };
+        outcomes.push(outcome);
+        i += 1;
+    }
+
+    Ok(ForkReport { height, outcomes })
+}
+
+// Classify the divergence of the witness at index witness_index,
+// whose header at the height of `verified` differs from the primary's.
+fn examine_divergence<C, H, L, V, R, W, K>(
+    common: &TrustedState<C, H>,
+    verified: &TrustedState<C, H>,
+    primary: &R,
+    witness: &W,
+    witness_index: usize,
+    options: &Options<L, V>,
+    clock: &K,
+) -> Divergence<C, H>
+    where
+        H: Header,
+        C: Commit,
+        L: TrustThreshold,
+        V: VerificationPredicates,
+        R: Requester<C, H>,
+        W: Requester<C, H>,
+        K: Clock,
+{
+    let verified_header = verified.last_header().header();
+    let height = verified_header.height();
+
+    // verify the witness' trace from the common trusted state
+    let witness_states = match verify_bisection(
+        common.clone(),
+        height,
+        options,
+        clock,
+        witness,
+    ) {
+        Ok(witness_states) => witness_states,
+        Err(error) => {
+            return Divergence::FaultyWitness {
+                error: error.from_provider(Provider::Witness(witness_index)),
+            }
+        }
+    };
+
+    // the witness' header verifies: make sure the primary still stands
+    // by the header we verified with it.
+    match primary.signed_header(height) {
+        Err(error) => {
+            return Divergence::FaultyPrimary {
+                error: error.from_provider(Provider::Primary),
+            }
+        }
+        Ok(primary_header) => {
+            if primary_header.header().hash() != verified_header.hash() {
+                return Divergence::FaultyPrimary {
+                    error: Error::from(Kind::ConflictingHeaders { height })
+                        .from_provider(Provider::Primary),
+                };
+            }
+        }
+    }
+
+    let mut witness_trace = Vec::with_capacity(witness_states.len().saturating_add(1));
+    witness_trace.push(common.clone());
+    witness_trace.extend(witness_states);
+    Divergence::Attack { witness_trace }
+}
+
+/// Outcome of the submission of evidence to a single provider.
+pub struct Submission {
+    provider: Provider,
+    result: Result<Hash, Error>,
+}
+
+impl Submission {
+    /// The provider the evidence was submitted to or, if it could not be built,
+    /// the provider whose data it was built from.
+    pub fn provider(&self) -> Provider {
+        self.provider
+    }
+
+    /// The hash of the evidence if the provider accepted it, or the error which
+    /// prevented building or submitting the evidence.
+    pub fn result(&self) -> &Result<Hash, Error> {
+        &self.result
+    }
+}
+
+/// Report the light client attacks found by [`detect_forks`].
+///
+/// For each witness revealing an attack, two pieces of evidence are built:
+/// the evidence that the witness' header conflicts with the primary trace,
+/// which is submitted to the primary, and the evidence that the primary's
+/// header conflicts with the witness trace, which is submitted to every witness.
+/// Each provider is sent a given piece of evidence, ie. a conflicting header
+/// and common height, only once, even if several witnesses revealed it.
+///
+/// Submissions are independent from each other: the outcome of each
+/// of them is returned, in the order they were made.
+/// The witnesses must be the ones the report was made with.
+#[trusted]
+pub fn report_attacks<C, H, R, W>(
+    primary_trace: &[TrustedState<C, H>],
+    report: &ForkReport<C, H>,
+    primary: &R,
+    witnesses: &[W],
+) -> Vec<Submission>
+    where
+        H: Header,
+        C: Commit,
+        R: EvidenceReporter<C, H>,
+        W: EvidenceReporter<C, H>,
+{
+    let height = report.height();
+    let mut submissions = Vec::new();
+    let mut submitted = Vec::new();
+    let mut i = 0;
+    while i < report.outcomes().len() {
+        if let WitnessOutcome::Diverged {
+            divergence: Divergence::Attack { witness_trace },
+            ..
+        } = &report.outcomes()[i]
+        {
+            // the height of the validators giving the total voting power of the evidence
+            let common_height = common_height(primary_trace, witness_trace);
+
+            // the witness' header, conflicting with the primary trace
+            let evidence = build_evidence(
+                primary_trace,
+                witness_trace,
+                common_height,
+                &witnesses[i],
+                height,
+            );
+            match evidence {
+                Ok(evidence) => submit_once(
+                    Provider::Primary,
+                    primary,
+                    &evidence,
+                    &mut submitted,
+                    &mut submissions,
+                ),
+                Err(error) => submissions.push(Submission {
+                    provider: Provider::Witness(i),
+                    result: Err(error.from_provider(Provider::Witness(i))),
+                }),
+            }
+
+            // the primary's header, conflicting with the witness trace
+            let evidence = build_evidence(
+                witness_trace,
+                primary_trace,
+                common_height,
+                primary,
+                height,
+            );
+            match evidence {
+                Ok(evidence) => {
+                    let mut j = 0;
+                    while j < witnesses.len() {
+                        submit_once(
+                            Provider::Witness(j),
+                            &witnesses[j],
+                            &evidence,
+                            &mut submitted,
+                            &mut submissions,
+                        );
+                        j += 1;
+                    }
+                }
+                Err(error) => submissions.push(Submission {
+                    provider: Provider::Primary,
+                    result: Err(error.from_provider(Provider::Primary)),
+                }),
+            }
+        }
+        i += 1;
+    }
+    submissions
+}
+
+// Build the evidence that the last header of conflicting_trace conflicts with
+// trusted_trace, fetching the validators it needs from the provider of
+// conflicting_trace.
+#[trusted]
+fn build_evidence<C, H, R>(
+    trusted_trace: &[TrustedState<C, H>],
+    conflicting_trace: &[TrustedState<C, H>],
+    common_height: Option<Height>,
+    conflicting_provider: &R,
+    height: Height,
+) -> Result<LightClientAttackEvidence<C, H>, Error>
+    where
+        H: Header,
+        C: Commit,
+        R: Requester<C, H>,
+{
+    let common_height = match common_height {
+        Some(common_height) => common_height,
+        None => return Err(Kind::InvalidEvidence.into()),
+    };
+    let vals = conflicting_provider.validator_set(height)?;
+    let common_vals = conflicting_provider.validator_set(common_height)?;
+    LightClientAttackEvidence::from_traces(trusted_trace, conflicting_trace, &vals, &common_vals)
+}
+
+// Submit the evidence to the provider, unless it was already submitted to it.
+// submitted holds the provider, conflicting header hash and common height
+// of every submission made so far.
+#[trusted]
+fn submit_once<C, H, R>(
+    provider: Provider,
+    reporter: &R,
+    evidence: &LightClientAttackEvidence<C, H>,
+    submitted: &mut Vec<(Provider, Hash, Height)>,
+    submissions: &mut Vec<Submission>,
+)
+    where
+        H: Header,
+        C: Commit,
+        R: EvidenceReporter<C, H>,
+{
+    let key = (
+        provider,
+        evidence.conflicting_header().header().hash(),
+        evidence.common_height(),
+    );
+    if submitted.contains(&key) {
+        return;
+    }
+    submitted.push(key);
+    submissions.push(Submission {
+        provider,
+        result: reporter.report_evidence(evidence),
+    });
+}
+
+/// Cross-check the last state of the given primary trace against each witness
+/// with [`detect_forks`], and report the light client attacks found, if any,
+/// with [`report_attacks`].
+pub fn detect_and_report_forks<C, H, L, V, R, W, K>(
+    primary_trace: &[TrustedState<C, H>],
+    primary: &R,
+    witnesses: &[W],
+    options: &Options<L, V>,
+    clock: &K,
+) -> Result<(ForkReport<C, H>, Vec<Submission>), Error>
+    where
+        H: Header,
+        C: Commit,
+        L: TrustThreshold,
+        V: VerificationPredicates,
+        R: EvidenceReporter<C, H>,
+        W: EvidenceReporter<C, H>,
+        K: Clock,
+{
+    let report = detect_forks(
+        primary_trace,
+        primary,
+        witnesses,
+        options,
+        clock,
+    )?;
+    let submissions = report_attacks(primary_trace, &report, primary, witnesses);
+    Ok((report, submissions))
+}
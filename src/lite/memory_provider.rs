@@ -0,0 +1,113 @@
+//! A provider serving light blocks from memory, eg. to run a light client
+//! in-process against a fixed chain.
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+
+use hash::Hash;
+use lite::error::{Error, Kind};
+use lite::evidence::LightClientAttackEvidence;
+use lite::types::{
+    Commit, EvidenceReporter, Header, Height, Requester, SignedHeader, ValidatorSetImpl,
+};
+
+/// MemoryProvider answers requests from the signed headers and validator sets
+/// it has been given. Requests for any other height fail with [`Kind::RequestFailed`],
+/// caused by a message telling what is missing.
+pub struct MemoryProvider<C, H>
+where
+    C: Commit,
+    H: Header,
+{
+    signed_headers: BTreeMap<Height, SignedHeader<C, H>>,
+    validator_sets: BTreeMap<Height, ValidatorSetImpl>,
+    reported_evidence: Cell<usize>,
+}
+
+impl<C, H> MemoryProvider<C, H>
+where
+    C: Commit,
+    H: Header,
+{
+    #[trusted]
+    pub fn new() -> Self {
+        Self {
+            signed_headers: BTreeMap::new(),
+            validator_sets: BTreeMap::new(),
+            reported_evidence: Cell::new(0),
+        }
+    }
+
+    /// Serve the given signed header at its height.
+    #[trusted]
+    pub fn add_signed_header(&mut self, signed_header: SignedHeader<C, H>) {
+        let h = signed_header.header().height();
+        self.signed_headers.insert(h, signed_header);
+    }
+
+    /// Serve the given validator set at height h.
+    #[trusted]
+    pub fn add_validator_set(&mut self, h: Height, validators: ValidatorSetImpl) {
+        self.validator_sets.insert(h, validators);
+    }
+
+    /// Number of pieces of evidence reported to this provider.
+    #[trusted]
+    pub fn reported_evidence(&self) -> usize {
+        self.reported_evidence.get()
+    }
+}
+
+impl<C, H> Default for MemoryProvider<C, H>
+where
+    C: Commit,
+    H: Header,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C, H> Requester<C, H> for MemoryProvider<C, H>
+where
+    C: Commit,
+    H: Header,
+{
+    #[trusted]
+    fn signed_header(&self, h: Height) -> Result<SignedHeader<C, H>, Error> {
+        match self.signed_headers.get(&h) {
+            Some(signed_header) => Ok(signed_header.clone()),
+            None => Err(Kind::RequestFailed.context(format!("no signed header at height {}", h))),
+        }
+    }
+
+    #[trusted]
+    fn validator_set(&self, h: Height) -> Result<ValidatorSetImpl, Error> {
+        match self.validator_sets.get(&h) {
+            Some(validators) => Ok(validators.clone()),
+            None => Err(Kind::RequestFailed.context(format!("no validator set at height {}", h))),
+        }
+    }
+
+    #[trusted]
+    fn latest_height(&self) -> Result<Height, Error> {
+        self.signed_headers
+            .keys()
+            .next_back()
+            .cloned()
+            .ok_or_else(|| Kind::RequestFailed.context("no signed header"))
+    }
+}
+
+impl<C, H> EvidenceReporter<C, H> for MemoryProvider<C, H>
+where
+    C: Commit,
+    H: Header,
+{
+    /// The evidence is only counted. Its hash is the hash of the conflicting header.
+    #[trusted]
+    fn report_evidence(&self, evidence: &LightClientAttackEvidence<C, H>) -> Result<Hash, Error> {
+        self.reported_evidence.set(self.reported_evidence.get() + 1);
+        Ok(evidence.conflicting_header().header().hash())
+    }
+}
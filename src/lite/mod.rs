@@ -1,5 +1,19 @@
 //! Core logic and traits of a light client.
 
+pub mod cache;
+pub mod churn;
+pub mod clock;
 pub mod error;
+pub mod evidence;
+pub mod file_store;
+pub mod fork_detector;
+pub mod memory_provider;
+pub mod options;
+pub mod predicates;
+pub mod proto;
+pub mod store;
+pub mod supervisor;
+#[cfg(test)]
+mod test_utils;
 pub mod types;
 pub mod verifier;
\ No newline at end of file
@@ -0,0 +1,187 @@
+//! Options of the light client verification, validated against each other.
+
+use std::time::Duration;
+
+use lite::error::{Error, Kind};
+use lite::predicates::{ProdPredicates, VerificationPredicates};
+use lite::types::TrustThreshold;
+
+/// Default maximum difference between the time of a fetched header and our clock.
+pub const DEFAULT_MAX_CLOCK_DRIFT: Duration = Duration::from_secs(10);
+
+/// Default maximum age of the latest header of a provider.
+pub const DEFAULT_MAX_BLOCK_LAG: Duration = Duration::from_secs(60);
+
+/// How the verifier gets from a trusted height to a higher untrusted height.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum VerificationMode {
+    /// Verify every intermediate height, one after the other.
+    Sequential,
+    /// Skip as many heights as the trust threshold allows,
+    /// bisecting when it does not allow to skip.
+    Skipping,
+    /// Skip as far as the validator churn observed on the previous skip
+    /// attempts suggests the trust threshold allows, see [`ChurnHistory`],
+    /// rather than always trying the untrusted height first.
+    ///
+    /// [`ChurnHistory`]: super::churn::ChurnHistory
+    Adaptive,
+}
+
+/// Options holds the parameters of the verification, and the predicates
+/// it checks, which are the production ones unless specified otherwise.
+/// It can only be built with an [`OptionsBuilder`], which validates them.
+#[derive(Copy, Clone)]
+pub struct Options<L, V = ProdPredicates>
+where
+    L: TrustThreshold,
+    V: VerificationPredicates,
+{
+    trust_threshold: L,
+    trusting_period: Duration,
+    max_clock_drift: Duration,
+    max_block_lag: Duration,
+    mode: VerificationMode,
+    predicates: V,
+}
+
+impl<L, V> Options<L, V>
+where
+    L: TrustThreshold,
+    V: VerificationPredicates,
+{
+    /// How much of the trusted validators' power must sign an untrusted
+    /// header to skip to it.
+    pub fn trust_threshold(&self) -> L {
+        self.trust_threshold
+    }
+
+    /// How long a header stays trusted after its time.
+    pub fn trusting_period(&self) -> Duration {
+        self.trusting_period
+    }
+
+    /// How far in the future of our clock the time of a fetched header may be.
+    pub fn max_clock_drift(&self) -> Duration {
+        self.max_clock_drift
+    }
+
+    /// How far in the past of our clock the time of the latest header
+    /// of a provider may be.
+    pub fn max_block_lag(&self) -> Duration {
+        self.max_block_lag
+    }
+
+    pub fn mode(&self) -> VerificationMode {
+        self.mode
+    }
+
+    /// The checks made on untrusted headers.
+    pub fn predicates(&self) -> &V {
+        &self.predicates
+    }
+}
+
+/// OptionsBuilder builds [`Options`], with the default clock drift, block lag,
+/// skipping verification and production predicates unless specified otherwise.
+pub struct OptionsBuilder<L, V = ProdPredicates>
+where
+    L: TrustThreshold,
+    V: VerificationPredicates,
+{
+    trust_threshold: L,
+    trusting_period: Duration,
+    max_clock_drift: Duration,
+    max_block_lag: Duration,
+    mode: VerificationMode,
+    unbonding_period: Option<Duration>,
+    predicates: V,
+}
+
+impl<L> OptionsBuilder<L>
+where
+    L: TrustThreshold,
+{
+    pub fn new(trust_threshold: L, trusting_period: Duration) -> Self {
+        Self {
+            trust_threshold,
+            trusting_period,
+            max_clock_drift: DEFAULT_MAX_CLOCK_DRIFT,
+            max_block_lag: DEFAULT_MAX_BLOCK_LAG,
+            mode: VerificationMode::Skipping,
+            unbonding_period: None,
+            predicates: ProdPredicates,
+        }
+    }
+}
+
+impl<L, V> OptionsBuilder<L, V>
+where
+    L: TrustThreshold,
+    V: VerificationPredicates,
+{
+    /// Check untrusted headers with the given predicates instead of the production ones.
+    pub fn predicates<W>(self, predicates: W) -> OptionsBuilder<L, W>
+    where
+        W: VerificationPredicates,
+    {
+        OptionsBuilder {
+            trust_threshold: self.trust_threshold,
+            trusting_period: self.trusting_period,
+            max_clock_drift: self.max_clock_drift,
+            max_block_lag: self.max_block_lag,
+            mode: self.mode,
+            unbonding_period: self.unbonding_period,
+            predicates,
+        }
+    }
+
+    pub fn max_clock_drift(mut self, max_clock_drift: Duration) -> Self {
+        self.max_clock_drift = max_clock_drift;
+        self
+    }
+
+    pub fn max_block_lag(mut self, max_block_lag: Duration) -> Self {
+        self.max_block_lag = max_block_lag;
+        self
+    }
+
+    pub fn mode(mut self, mode: VerificationMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// The unbonding period of the chain: the trusting period must be shorter,
+    /// otherwise validators could misbehave after they unbonded without being punished.
+    pub fn unbonding_period(mut self, unbonding_period: Duration) -> Self {
+        self.unbonding_period = Some(unbonding_period);
+        self
+    }
+
+    /// Build the options if they are consistent with each other.
+    ///
+    /// Returns [`Kind::InvalidTrustingPeriod`] if the trusting period is zero or
+    /// not shorter than the unbonding period, if any, and [`Kind::InvalidClockDrift`]
+    /// if the maximum clock drift is not shorter than the trusting period.
+    pub fn build(self) -> Result<Options<L, V>, Error> {
+        if self.trusting_period == Duration::from_secs(0) {
+            return Err(Kind::InvalidTrustingPeriod.into());
+        }
+        if let Some(unbonding_period) = self.unbonding_period {
+            if self.trusting_period >= unbonding_period {
+                return Err(Kind::InvalidTrustingPeriod.into());
+            }
+        }
+        if self.max_clock_drift >= self.trusting_period {
+            return Err(Kind::InvalidClockDrift.into());
+        }
+        Ok(Options {
+            trust_threshold: self.trust_threshold,
+            trusting_period: self.trusting_period,
+            max_clock_drift: self.max_clock_drift,
+            max_block_lag: self.max_block_lag,
+            mode: self.mode,
+            predicates: self.predicates,
+        })
+    }
+}
@@ -0,0 +1,276 @@
+//! The individual checks made by the verifier, as an overridable trait.
+
+use lite::error::{Error, Kind};
+use lite::types::{
+    Commit, Header, TrustThreshold, ValidatorSetImpl, MAX_TOTAL_VOTING_POWER,
+};
+use lite::verifier::{Verdict, VotingPowerTally};
+
+/// VerificationPredicates holds every check the verifier makes on an untrusted
+/// header and the validators served along with it.
+///
+/// Each predicate has a default implementation, the rule of production chains,
+/// see [`ProdPredicates`]. Test networks or custom chains can implement this trait
+/// and override individual predicates, eg. [`has_sufficient_signers_overlap`]
+/// to change the +2/3 rule, and pass it in the [`Options`] of the verification.
+///
+/// [`has_sufficient_signers_overlap`]: VerificationPredicates::has_sufficient_signers_overlap
+/// [`Options`]: super::options::Options
+pub trait VerificationPredicates: Copy + Clone {
+    /// The total voting power of the validators is at most [`MAX_TOTAL_VOTING_POWER`],
+    /// so that the voting power arithmetic cannot overflow.
+    fn voting_power_within_bounds(&self, vals: &ValidatorSetImpl) -> Result<(), Error> {
+        if vals.total_power() > MAX_TOTAL_VOTING_POWER {
+            return Err(Kind::TotalVotingPowerTooLarge {
+                total: vals.total_power(),
+            }.into());
+        }
+        Ok(())
+    }
+
+    /// The validators hash of the header is the hash of the given validators.
+    fn validator_sets_match<H>(&self, header: &H, vals: &ValidatorSetImpl) -> Result<(), Error>
+    where
+        H: Header,
+    {
+        if header.validators_hash() != vals.hash() {
+            return Err(Kind::InvalidValidatorSet {
+                header_val_hash: header.validators_hash(),
+                val_hash: vals.hash(),
+            }.into());
+        }
+        Ok(())
+    }
+
+    /// The next validators hash of the header is the hash of the given next validators.
+    fn next_validators_match<H>(
+        &self,
+        header: &H,
+        next_vals: &ValidatorSetImpl,
+    ) -> Result<(), Error>
+    where
+        H: Header,
+    {
+        if header.next_validators_hash() != next_vals.hash() {
+            return Err(Kind::InvalidNextValidatorSet {
+                header_next_val_hash: header.next_validators_hash(),
+                next_val_hash: next_vals.hash(),
+            }.into());
+        }
+        Ok(())
+    }
+
+    /// The commit is for the header.
+    fn header_matches_commit<H, C>(&self, header: &H, commit: &C) -> Result<(), Error>
+    where
+        H: Header,
+        C: Commit,
+    {
+        if header.hash() != commit.header_hash() {
+            return Err(Kind::InvalidCommitValue {
+                header_hash: header.hash(),
+                commit_hash: commit.header_hash(),
+            }.into());
+        }
+        Ok(())
+    }
+
+    /// The additional validation of the commit against its validators,
+    /// see [`Commit::validate`].
+    fn valid_commit<C>(&self, commit: &C, vals: &ValidatorSetImpl) -> Result<(), Error>
+    where
+        C: Commit,
+    {
+        commit.validate(vals)
+    }
+
+    /// The time of the untrusted header is after the time of the trusted header.
+    fn is_monotonic_bft_time<H>(&self, untrusted: &H, trusted: &H) -> Result<(), Error>
+    where
+        H: Header,
+    {
+        if untrusted.bft_time() <= trusted.bft_time() {
+            return Err(Kind::NonIncreasingTime.into());
+        }
+        Ok(())
+    }
+
+    /// The height of the untrusted header is above the height of the trusted header.
+    fn is_monotonic_height<H>(&self, untrusted: &H, trusted: &H) -> Result<(), Error>
+    where
+        H: Header,
+    {
+        if untrusted.height() <= trusted.height() {
+            let expected = match trusted.height().checked_add(1) {
+                Some(expected) => expected,
+                None => return Err(Kind::ImplementationSpecific.context("height overflow")),
+            };
+            return Err(Kind::NonIncreasingHeight {
+                got: untrusted.height(),
+                expected,
+            }.into());
+        }
+        Ok(())
+    }
+
+    /// For adjacent headers: the validators of the untrusted header are
+    /// the next validators of the trusted header.
+    fn valid_validator_set_link<H>(&self, untrusted: &H, trusted: &H) -> Result<(), Error>
+    where
+        H: Header,
+    {
+        if trusted.next_validators_hash() != untrusted.validators_hash() {
+            return Err(Kind::InvalidValidatorSetLink {
+                trusted_height: trusted.height(),
+                trusted_next_val_hash: trusted.next_validators_hash(),
+                untrusted_height: untrusted.height(),
+                untrusted_val_hash: untrusted.validators_hash(),
+            }.into());
+        }
+        Ok(())
+    }
+
+    /// More than 2/3 of the power of the validators of the commit signed it.
+    /// NOTE: These validators are expected to be the correct validators for the commit,
+    /// but since we're using voting_power_in, we can't actually detect if there's
+    /// votes from validators not in the set.
+    fn has_sufficient_signers_overlap<C>(
+        &self,
+        commit: &C,
+        vals: &ValidatorSetImpl,
+    ) -> Result<(), Error>
+    where
+        C: Commit,
+    {
+        let total_power = vals.total_power();
+        let signed_power = commit.voting_power_in(vals)?;
+
+        // in u128, where the products cannot overflow
+        if (signed_power as u128) * 3 <= (total_power as u128) * 2 {
+            return Err(Kind::InvalidCommit {
+                total: total_power,
+                signed: signed_power,
+            }.into());
+        }
+        Ok(())
+    }
+
+    /// Enough of the power of the trusted validators, and enough of them,
+    /// signed the commit to skip to its header, according to the trust threshold.
+    /// NOTE the trusted validators do not necessarily correspond to the validator set
+    /// of this commit, but there may be some intersection.
+    ///
+    /// Returns [`Verdict::NotEnoughTrust`] with the voting power tally if they did not.
+    fn has_sufficient_validators_overlap<C, L>(
+        &self,
+        commit: &C,
+        trusted_vals: &ValidatorSetImpl,
+        trust_threshold: L,
+    ) -> Verdict
+    where
+        C: Commit,
+        L: TrustThreshold,
+    {
+        let total_power = trusted_vals.total_power();
+        let signed_power = match commit.voting_power_in(trusted_vals) {
+            Ok(signed_power) => signed_power,
+            Err(e) => return Verdict::Invalid(e),
+        };
+
+        let signers = trusted_vals.count_members(&commit.signers());
+
+        if !trust_threshold.is_enough_power(signed_power, total_power)
+            || !trust_threshold.is_enough_signers(signers)
+        {
+            return Verdict::NotEnoughTrust(VotingPowerTally {
+                total: total_power,
+                signed: signed_power,
+                signers,
+            });
+        }
+        Verdict::Success
+    }
+}
+
+/// The predicates of production chains: the default implementation of
+/// every [`VerificationPredicates`].
+#[derive(Copy, Clone, Default)]
+pub struct ProdPredicates;
+
+impl VerificationPredicates for ProdPredicates {}
+
+#[cfg(test)]
+mod tests {
+    use lite::error::Kind;
+    use lite::test_utils::{address, hash, TestCommit};
+    use lite::types::{
+        TrustThresholdFraction, Validator, ValidatorSetImpl, MAX_TOTAL_VOTING_POWER,
+    };
+    use lite::verifier::Verdict;
+
+    use super::{ProdPredicates, VerificationPredicates};
+
+    // Three validators with a third of the maximum total voting power each.
+    fn heavy_validators() -> ValidatorSetImpl {
+        let validators = (0..3)
+            .map(|n| Validator::new(address(n), MAX_TOTAL_VOTING_POWER / 3))
+            .collect();
+        ValidatorSetImpl::new(hash(1, 0), validators)
+    }
+
+    fn commit(signers: &[u64]) -> TestCommit {
+        TestCommit {
+            header_hash: hash(5, 1),
+            signers: signers.iter().map(|n| address(*n)).collect(),
+        }
+    }
+
+    #[test]
+    fn commit_checks_near_the_maximum_total_power() {
+        let vals = heavy_validators();
+        assert_eq!(vals.total_power(), MAX_TOTAL_VOTING_POWER);
+        assert!(ProdPredicates.voting_power_within_bounds(&vals).is_ok());
+
+        match ProdPredicates.has_sufficient_signers_overlap(&commit(&[0, 1]), &vals) {
+            Err(e) => match e.kind() {
+                Kind::InvalidCommit { .. } => (),
+                kind => panic!("unexpected error {:?}", kind),
+            },
+            Ok(()) => panic!("accepted a commit signed by exactly 2/3"),
+        }
+        let signed = ProdPredicates.has_sufficient_signers_overlap(&commit(&[0, 1, 2]), &vals);
+        assert!(signed.is_ok());
+
+        let threshold = TrustThresholdFraction::default();
+        match ProdPredicates.has_sufficient_validators_overlap(&commit(&[0]), &vals, threshold) {
+            Verdict::NotEnoughTrust(tally) => {
+                assert_eq!(tally.total, MAX_TOTAL_VOTING_POWER);
+                assert_eq!(tally.signed, MAX_TOTAL_VOTING_POWER / 3);
+                assert_eq!(tally.signers, 1);
+            }
+            _ => panic!("skipped with exactly 1/3 of the trusted power"),
+        }
+        match ProdPredicates.has_sufficient_validators_overlap(&commit(&[0, 1]), &vals, threshold) {
+            Verdict::Success => (),
+            _ => panic!("did not skip with 2/3 of the trusted power"),
+        }
+    }
+
+    #[test]
+    fn commit_checks_above_the_maximum_total_power() {
+        let mut validators = heavy_validators().validators().to_vec();
+        validators.push(Validator::new(address(3), u64::max_value()));
+        let vals = ValidatorSetImpl::new(hash(1, 1), validators);
+        assert_eq!(vals.total_power(), u64::max_value());
+        match ProdPredicates.voting_power_within_bounds(&vals) {
+            Err(e) => match e.kind() {
+                Kind::TotalVotingPowerTooLarge { total } => assert_eq!(*total, u64::max_value()),
+                kind => panic!("unexpected error {:?}", kind),
+            },
+            Ok(()) => panic!("accepted a total voting power above the maximum"),
+        }
+        // the checks themselves do not overflow
+        let signed = ProdPredicates.has_sufficient_signers_overlap(&commit(&[0, 1, 2, 3]), &vals);
+        assert!(signed.is_ok());
+    }
+}
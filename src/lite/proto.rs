@@ -0,0 +1,86 @@
+//! Minimal encoder for the Tendermint protobuf messages the light client
+//! sends to full nodes, eg. evidence of misbehaviour.
+//!
+//! Only the wire format is implemented here: the messages themselves
+//! are laid out by the types they encode.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use lite::types::{Validator, ValidatorSetImpl};
+
+/// EncodeProto is implemented by the types which can be encoded to their
+/// Tendermint protobuf message. As [`super::types::Header`] and
+/// [`super::types::Commit`] are left to the implementation, so is their
+/// encoding to `tendermint.types.Header` and `tendermint.types.Commit`.
+pub trait EncodeProto {
+    /// The encoded message, without its own tag and length.
+    fn encode_proto(&self) -> Vec<u8>;
+}
+
+const WIRE_VARINT: u64 = 0;
+const WIRE_LENGTH_DELIMITED: u64 = 2;
+
+/// Append the given unsigned varint.
+#[trusted]
+pub fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn put_key(buf: &mut Vec<u8>, field: u32, wire_type: u64) {
+    put_varint(buf, (u64::from(field) << 3) | wire_type);
+}
+
+/// Append an `int64`/`uint64` field. Default (zero) values are omitted.
+pub fn put_u64_field(buf: &mut Vec<u8>, field: u32, value: u64) {
+    if value != 0 {
+        put_key(buf, field, WIRE_VARINT);
+        put_varint(buf, value);
+    }
+}
+
+/// Append a `bytes` or embedded message field.
+pub fn put_bytes_field(buf: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+    put_key(buf, field, WIRE_LENGTH_DELIMITED);
+    put_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+/// Encode the given time as a `google.protobuf.Timestamp`.
+/// Times before the unix epoch are encoded as the epoch.
+#[trusted]
+pub fn encode_timestamp(time: SystemTime) -> Vec<u8> {
+    let mut buf = Vec::new();
+    if let Ok(since_epoch) = time.duration_since(UNIX_EPOCH) {
+        put_u64_field(&mut buf, 1, since_epoch.as_secs());
+        put_u64_field(&mut buf, 2, u64::from(since_epoch.subsec_nanos()));
+    }
+    buf
+}
+
+impl EncodeProto for Validator {
+    /// `tendermint.types.Validator`. The public key is not known to the
+    /// light client and is left out.
+    fn encode_proto(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        put_bytes_field(&mut buf, 1, self.address().as_bytes());
+        put_u64_field(&mut buf, 3, self.power());
+        buf
+    }
+}
+
+impl EncodeProto for ValidatorSetImpl {
+    /// `tendermint.types.ValidatorSet`.
+    #[trusted]
+    fn encode_proto(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for validator in self.validators() {
+            put_bytes_field(&mut buf, 1, &validator.encode_proto());
+        }
+        put_u64_field(&mut buf, 3, self.total_power());
+        buf
+    }
+}
@@ -0,0 +1,155 @@
+//! Storage of the light blocks known to a light client, together with
+//! their verification status.
+
+use std::collections::BTreeMap;
+
+use lite::error::{Error, Kind};
+use lite::types::{Commit, Header, Height, TrustedState};
+
+/// Verification status of a light block held in a [`LightStore`].
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Status {
+    /// The light block has been fetched but not verified yet.
+    Unverified,
+    /// The light block has been verified against a trusted or verified block.
+    Verified,
+    /// The light block has been verified and cross-checked, or was
+    /// configured subjectively by the operator.
+    Trusted,
+    /// The verification of the light block failed.
+    Failed,
+    /// The light block was trusted or verified, but expired and was
+    /// superseded by a new subjective initialization. It is kept for the record.
+    Archived,
+}
+
+impl Status {
+    /// Whether a light block with this status can be used as the trusted
+    /// state from which another light block is verified.
+    #[pure]
+    pub fn is_trusted_or_verified(&self) -> bool {
+        match self {
+            Status::Verified | Status::Trusted => true,
+            Status::Unverified | Status::Failed | Status::Archived => false,
+        }
+    }
+}
+
+/// LightStore holds light blocks, ie. [`TrustedState`]s, keyed by the
+/// height of their header. At most one light block is stored per height.
+pub trait LightStore<C, H>
+where
+    C: Commit,
+    H: Header,
+{
+    /// The light block stored at height h and its status, if any.
+    fn get(&self, h: Height) -> Option<(TrustedState<C, H>, Status)>;
+
+    /// Store the given light block with the given status, replacing the
+    /// light block previously stored at the same height, if any.
+    fn insert(&mut self, state: TrustedState<C, H>, status: Status) -> Result<(), Error>;
+
+    /// Change the status of the light block stored at height h.
+    /// Returns [`Kind::NotInStore`] if there is no such light block.
+    fn update_status(&mut self, h: Height, status: Status) -> Result<(), Error>;
+
+    /// Remove the light block stored at height h, returning it if any.
+    fn remove(&mut self, h: Height) -> Result<Option<(TrustedState<C, H>, Status)>, Error>;
+
+    /// The highest light block with the [`Status::Trusted`] status.
+    fn latest_trusted(&self) -> Option<TrustedState<C, H>>;
+
+    /// The highest light block strictly below height h which is either
+    /// trusted or verified, ie. the closest block from which h can be verified.
+    fn highest_below(&self, h: Height) -> Option<TrustedState<C, H>>;
+}
+
+/// LightStore implementation keeping all light blocks in memory.
+pub struct MemoryStore<C, H>
+where
+    C: Commit,
+    H: Header,
+{
+    blocks: BTreeMap<Height, (TrustedState<C, H>, Status)>,
+}
+
+impl<C, H> MemoryStore<C, H>
+where
+    C: Commit,
+    H: Header,
+{
+    #[trusted]
+    pub fn new() -> Self {
+        Self {
+            blocks: BTreeMap::new(),
+        }
+    }
+
+    /// All stored light blocks and their status, by increasing height.
+    #[trusted]
+    pub fn entries(&self) -> Vec<(TrustedState<C, H>, Status)> {
+        self.blocks.values().cloned().collect()
+    }
+}
+
+impl<C, H> Default for MemoryStore<C, H>
+where
+    C: Commit,
+    H: Header,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C, H> LightStore<C, H> for MemoryStore<C, H>
+where
+    C: Commit,
+    H: Header,
+{
+    #[trusted]
+    fn get(&self, h: Height) -> Option<(TrustedState<C, H>, Status)> {
+        self.blocks.get(&h).cloned()
+    }
+
+    #[trusted]
+    fn insert(&mut self, state: TrustedState<C, H>, status: Status) -> Result<(), Error> {
+        let h = state.last_header().header().height();
+        self.blocks.insert(h, (state, status));
+        Ok(())
+    }
+
+    #[trusted]
+    fn update_status(&mut self, h: Height, status: Status) -> Result<(), Error> {
+        match self.blocks.get_mut(&h) {
+            Some(entry) => {
+                entry.1 = status;
+                Ok(())
+            }
+            None => Err(Kind::NotInStore { height: h }.into()),
+        }
+    }
+
+    #[trusted]
+    fn remove(&mut self, h: Height) -> Result<Option<(TrustedState<C, H>, Status)>, Error> {
+        Ok(self.blocks.remove(&h))
+    }
+
+    #[trusted]
+    fn latest_trusted(&self) -> Option<TrustedState<C, H>> {
+        self.blocks
+            .values()
+            .rev()
+            .find(|(_, status)| *status == Status::Trusted)
+            .map(|(state, _)| state.clone())
+    }
+
+    #[trusted]
+    fn highest_below(&self, h: Height) -> Option<TrustedState<C, H>> {
+        self.blocks
+            .range(..h)
+            .rev()
+            .find(|(_, (_, status))| status.is_trusted_or_verified())
+            .map(|(_, (state, _))| state.clone())
+    }
+}
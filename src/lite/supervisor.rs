@@ -0,0 +1,588 @@
+//! The supervisor drives a light client: it owns the light store, syncs
+//! with a primary provider, cross-checks the verified headers against
+//! witness providers and replaces the primary by a witness when the
+//! primary fails or misbehaves.
+
+use hash::Hash;
+use lite::churn::{ChurnHistory, DEFAULT_CHURN_HISTORY_LEN};
+use lite::clock::Clock;
+use lite::error::{Error, Kind};
+use lite::fork_detector::{detect_and_report_forks, Divergence, Submission, WitnessOutcome};
+use lite::options::Options;
+use lite::predicates::VerificationPredicates;
+use lite::store::{LightStore, Status};
+use lite::types::{
+    Commit, EvidenceReporter, Header, Height, Provider, TrustThreshold, TrustedState,
+};
+use lite::verifier::{is_within_trust_period, verify_trace_to_height};
+
+/// Summary of the state of a [`Supervisor`].
+pub struct SupervisorStatus {
+    /// Height of the latest trusted state, if any.
+    pub latest_trusted_height: Option<Height>,
+    /// Number of witnesses left to cross-check against and to replace the primary.
+    pub witnesses: usize,
+    /// Number of times the primary has been replaced by a witness.
+    pub primary_replacements: usize,
+}
+
+/// Supervisor syncs a light store using a primary provider and a list of witnesses.
+///
+/// Every header verified with the primary is cross-checked against all witnesses
+/// before it is marked as [`Status::Trusted`] in the store. When the primary fails to
+/// answer, serves data which does not verify, or is found faulty by a witness,
+/// it is dropped and replaced by the first witness.
+/// Witnesses found faulty are dropped.
+/// In [`VerificationMode::Adaptive`], each sync picks the heights to skip to
+/// from the validator churn observed by the previous ones.
+///
+/// [`VerificationMode::Adaptive`]: super::options::VerificationMode::Adaptive
+pub struct Supervisor<C, H, L, V, P, S, K>
+where
+    C: Commit,
+    H: Header,
+    L: TrustThreshold,
+    V: VerificationPredicates,
+    P: EvidenceReporter<C, H>,
+    S: LightStore<C, H>,
+    K: Clock,
+{
+    store: S,
+    primary: P,
+    witnesses: Vec<P>,
+    options: Options<L, V>,
+    clock: K,
+    primary_replacements: usize,
+    submissions: Vec<Submission>,
+    // the validator churn observed by all syncs, for the adaptive verification mode
+    churn: ChurnHistory,
+    // the supervisor does not own any C nor H, only the store does.
+    _marker: ::std::marker::PhantomData<(C, H)>,
+}
+
+impl<C, H, L, V, P, S, K> Supervisor<C, H, L, V, P, S, K>
+where
+    C: Commit,
+    H: Header,
+    L: TrustThreshold,
+    V: VerificationPredicates,
+    P: EvidenceReporter<C, H>,
+    S: LightStore<C, H>,
+    K: Clock,
+{
+    /// The store must hold at least one trusted state to sync from.
+    pub fn new(
+        store: S,
+        primary: P,
+        witnesses: Vec<P>,
+        options: Options<L, V>,
+        clock: K,
+    ) -> Self {
+        Self {
+            store,
+            primary,
+            witnesses,
+            options,
+            clock,
+            primary_replacements: 0,
+            submissions: Vec::new(),
+            churn: ChurnHistory::new(DEFAULT_CHURN_HISTORY_LEN),
+            _marker: ::std::marker::PhantomData,
+        }
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub fn primary(&self) -> &P {
+        &self.primary
+    }
+
+    pub fn witnesses(&self) -> &[P] {
+        &self.witnesses
+    }
+
+    /// The highest trusted state of the store.
+    pub fn latest_trusted(&self) -> Option<TrustedState<C, H>> {
+        self.store.latest_trusted()
+    }
+
+    pub fn status(&self) -> SupervisorStatus {
+        SupervisorStatus {
+            latest_trusted_height: match self.store.latest_trusted() {
+                Some(state) => Some(state.last_header().header().height()),
+                None => None,
+            },
+            witnesses: self.witnesses.len(),
+            primary_replacements: self.primary_replacements,
+        }
+    }
+
+    /// Verify the latest height known to the primary, see [`Supervisor::verify_to_target`].
+    pub fn verify_to_highest(&mut self) -> Result<TrustedState<C, H>, Error> {
+        loop {
+            match self.primary.latest_height() {
+                Ok(target) => return self.verify_to_target(target),
+                Err(e) => self.replace_primary(e.from_provider(Provider::Primary))?,
+            }
+        }
+    }
+
+    /// Verify the given height with the primary, cross-check the verified header
+    /// against the witnesses and mark it as trusted in the store.
+    /// The witnesses verify their header from the same unexpired anchor as the primary,
+    /// see [`verify_trace_to_height`].
+    ///
+    /// Returns [`Kind::ConflictingHeaders`] if a light client attack is detected: the
+    /// evidence is then reported to all providers (see [`Supervisor::submissions`]),
+    /// and the header, as well as the intermediate headers verified with it, are marked as failed.
+    /// Returns [`Kind::NoWitnesses`] if the primary had to be replaced but no
+    /// witness was left, with the error of the primary as its source, or if
+    /// no witness served the verified header.
+    ///
+    /// Errors due to the light client itself rather than to the primary, eg. an
+    /// expired anchor, a store failure or a header from the future of our clock,
+    /// are returned as they are instead of replacing the primary.
+    pub fn verify_to_target(&mut self, target: Height) -> Result<TrustedState<C, H>, Error> {
+        loop {
+            if let Some((state, status)) = self.store.get(target) {
+                if status == Status::Trusted {
+                    return Ok(state);
+                }
+            }
+
+            // cross-check the very trace the primary was verified along, from
+            // an anchor which has not expired
+            let primary_trace = match verify_trace_to_height(
+                &mut self.store,
+                target,
+                &self.options,
+                &self.clock,
+                &self.primary,
+                &mut self.churn,
+            ) {
+                Ok(primary_trace) => primary_trace,
+                Err(e) => {
+                    if is_local_failure(&e) {
+                        return Err(e);
+                    }
+                    // the primary failed to answer or served data which does not verify
+                    self.replace_primary(e.from_provider(Provider::Primary))?;
+                    continue;
+                }
+            };
+
+            let (report, submissions) = detect_and_report_forks(
+                &primary_trace,
+                &self.primary,
+                &self.witnesses,
+                &self.options,
+                &self.clock,
+            )?;
+            self.submissions = submissions;
+
+            if report.has_attack() {
+                self.fail_trace(&primary_trace)?;
+                return Err(Kind::ConflictingHeaders { height: target }.into());
+            }
+
+            let mut faulty_primary = false;
+            let mut faulty_witnesses = Vec::new();
+            let mut confirmations: usize = 0;
+            let mut i = 0;
+            while i < report.outcomes().len() {
+                match &report.outcomes()[i] {
+                    WitnessOutcome::Agrees => confirmations = confirmations.saturating_add(1),
+                    WitnessOutcome::Unavailable { .. } => (),
+                    WitnessOutcome::Diverged { divergence, .. } => match divergence {
+                        Divergence::FaultyWitness { .. } => faulty_witnesses.push(i),
+                        Divergence::FaultyPrimary { .. } => faulty_primary = true,
+                        Divergence::Attack { .. } => (),
+                    },
+                }
+                i += 1;
+            }
+            // remove from the highest index down so the lower ones stay valid
+            while let Some(i) = faulty_witnesses.pop() {
+                self.witnesses.remove(i);
+            }
+
+            if faulty_primary {
+                self.fail_trace(&primary_trace)?;
+                let e = Error::from(Kind::ConflictingHeaders { height: target });
+                self.replace_primary(e.from_provider(Provider::Primary))?;
+                continue;
+            }
+
+            // the header stays verified until a witness vouches for it
+            if confirmations == 0 {
+                return Err(Kind::NoWitnesses.into());
+            }
+
+            let verified = match primary_trace.last() {
+                Some(verified) => verified.clone(),
+                None => return Err(Kind::ImplementationSpecific.context("empty verification trace")),
+            };
+            self.store.update_status(target, Status::Trusted)?;
+            return Ok(verified);
+        }
+    }
+
+    /// Outcome of the submission of the evidence of the light client attacks
+    /// found by the latest cross-check against the witnesses, if any,
+    /// see [`super::fork_detector::report_attacks`].
+    pub fn submissions(&self) -> &[Submission] {
+        &self.submissions
+    }
+
+    /// Whether the light client has to be reinitialized subjectively with
+    /// [`Supervisor::reinitialize`], ie. whether it has no trusted state or
+    /// its latest trusted state expired.
+    ///
+    /// Returns the error if the expiration of the latest trusted state cannot be
+    /// told, eg. [`Kind::ExpirationOutOfRange`] for a trusting period out of range.
+    pub fn needs_reinitialization(&self) -> Result<bool, Error> {
+        match self.store.latest_trusted() {
+            Some(state) => {
+                let header = state.last_header().header();
+                let trusting_period = self.options.trusting_period();
+                match is_within_trust_period(header, trusting_period, self.clock.now()) {
+                    Ok(()) => Ok(false),
+                    Err(e) => match e.kind() {
+                        Kind::Expired { .. } => Ok(true),
+                        _ => Err(e),
+                    },
+                }
+            }
+            None => Ok(true),
+        }
+    }
+
+    /// Reinitialize the light client from a new subjective checkpoint, once its
+    /// latest trusted state expired (see [`Supervisor::needs_reinitialization`]).
+    ///
+    /// The checkpoint is initialized with the primary, see [`TrustedState::init_from_options`],
+    /// and then cross-checked against the witnesses: all witnesses which answer must serve
+    /// the same header at the checkpoint height, and at least one of them must answer.
+    /// Only then are the previously trusted and verified states of the store archived
+    /// with the [`Status::Archived`] status, and the checkpoint stored as trusted.
+    ///
+    /// Returns [`Kind::NotExpired`] if the latest trusted state has not expired,
+    /// the errors of [`Supervisor::needs_reinitialization`],
+    /// [`Kind::ConflictingHeaders`] if a witness disagrees with the checkpoint or the store
+    /// holds another trusted or verified header at its height,
+    /// [`Kind::NoWitnesses`] if no witness answered, and the errors of
+    /// [`TrustedState::init_from_options`] otherwise.
+    pub fn reinitialize(&mut self, height: Height, hash: Hash) -> Result<TrustedState<C, H>, Error> {
+        if !self.needs_reinitialization()? {
+            return Err(Kind::NotExpired.into());
+        }
+
+        let checkpoint = TrustedState::init_from_options(
+            height,
+            hash,
+            &self.primary,
+            self.clock.now(),
+            &self.options,
+        ).map_err(|e| e.at_height(height).from_provider(Provider::Primary))?;
+
+        let mut confirmations: usize = 0;
+        let mut i = 0;
+        while i < self.witnesses.len() {
+            if let Ok(signed_header) = self.witnesses[i].signed_header(height) {
+                if signed_header.header().hash() != hash {
+                    return Err(Error::from(Kind::ConflictingHeaders { height })
+                        .from_provider(Provider::Witness(i)));
+                }
+                confirmations = confirmations.saturating_add(1);
+            }
+            i += 1;
+        }
+        if confirmations == 0 {
+            return Err(Kind::NoWitnesses.into());
+        }
+
+        // never replace a different header we trusted or verified at the same height
+        if let Some((stored, status)) = self.store.get(height) {
+            if status.is_trusted_or_verified() && stored.last_header().header().hash() != hash {
+                return Err(Kind::ConflictingHeaders { height }.into());
+            }
+        }
+
+        // archive the stale states, from the highest one down
+        let mut below = Height::max_value();
+        while let Some(stale) = self.store.highest_below(below) {
+            below = stale.last_header().header().height();
+            self.store.update_status(below, Status::Archived)?;
+        }
+
+        self.store.insert(checkpoint.clone(), Status::Trusted)?;
+        Ok(checkpoint)
+    }
+
+    // Mark every state of the primary trace above its anchor as failed,
+    // so that none of them is used as an anchor again.
+    fn fail_trace(&mut self, primary_trace: &[TrustedState<C, H>]) -> Result<(), Error> {
+        let mut i = 1;
+        while i < primary_trace.len() {
+            let height = primary_trace[i].last_header().header().height();
+            self.store.update_status(height, Status::Failed)?;
+            i += 1;
+        }
+        Ok(())
+    }
+
+    // Drop the primary, which failed with the given error, and promote the first
+    // witness in its place. If there is none, the error is the source of NoWitnesses.
+    fn replace_primary(&mut self, e: Error) -> Result<(), Error> {
+        if self.witnesses.is_empty() {
+            return Err(Kind::NoWitnesses.context(e));
+        }
+        self.primary = self.witnesses.remove(0);
+        self.primary_replacements = self.primary_replacements.saturating_add(1);
+        Ok(())
+    }
+}
+
+// Whether the error is due to the local state of the light client, including
+// its clock, rather than to the data served by the primary.
+fn is_local_failure(e: &Error) -> bool {
+    match e.kind() {
+        Kind::Expired { .. }
+        | Kind::ExpirationOutOfRange { .. }
+        | Kind::DurationOutOfRange
+        | Kind::HeaderFromFuture { .. }
+        | Kind::NoTrustedState { .. }
+        | Kind::NotInStore { .. }
+        | Kind::StoreFailed
+        | Kind::CorruptStore
+        | Kind::UnsupportedStoreVersion { .. } => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use lite::clock::MockClock;
+    use lite::error::{Error, Kind};
+    use lite::memory_provider::MemoryProvider;
+    use lite::options::OptionsBuilder;
+    use lite::predicates::ProdPredicates;
+    use lite::store::{LightStore, MemoryStore, Status};
+    use lite::test_utils::{
+        chain, forked_header, signed_by, signed_header, validators, TestCommit, TestHeader,
+    };
+    use lite::types::{Header, Height, SignedHeader, TrustThresholdFraction, TrustedState};
+
+    use super::Supervisor;
+
+    // No validator is replaced on the test chain.
+    const EVERY: u64 = 1000;
+    const LENGTH: Height = 10;
+    // In seconds, ie. in heights of the test chain.
+    const TRUSTING_PERIOD: u64 = 100;
+
+    type Provider = MemoryProvider<TestCommit, TestHeader>;
+    type TestSupervisor = Supervisor<
+        TestCommit,
+        TestHeader,
+        TrustThresholdFraction,
+        ProdPredicates,
+        Provider,
+        MemoryStore<TestCommit, TestHeader>,
+        MockClock,
+    >;
+
+    // A supervisor trusting the first header of the chain, whose clock
+    // is at the time of the last one.
+    fn new_supervisor(primary: Provider, witnesses: Vec<Provider>) -> TestSupervisor {
+        supervisor_at(LENGTH, primary, witnesses)
+    }
+
+    // A supervisor trusting the first header of the chain, whose clock
+    // is at the time of the header at height now.
+    fn supervisor_at(now: Height, primary: Provider, witnesses: Vec<Provider>) -> TestSupervisor {
+        let mut store = MemoryStore::new();
+        let trusted = TrustedState::new(&signed_header(1, EVERY), &validators(2, EVERY));
+        store.insert(trusted, Status::Trusted).unwrap();
+        let trusting_period = Duration::from_secs(TRUSTING_PERIOD);
+        let options = OptionsBuilder::new(TrustThresholdFraction::default(), trusting_period)
+            .max_clock_drift(Duration::from_secs(1))
+            .build()
+            .unwrap();
+        let clock = MockClock::new(UNIX_EPOCH + Duration::from_secs(now));
+        Supervisor::new(store, primary, witnesses, options, clock)
+    }
+
+    // The chain, except that its last header is the given one.
+    fn chain_ending_with(last: SignedHeader<TestCommit, TestHeader>) -> Provider {
+        let mut provider = chain(LENGTH, EVERY);
+        provider.add_signed_header(last);
+        provider
+    }
+
+    fn status(supervisor: &TestSupervisor, h: Height) -> Option<Status> {
+        supervisor.store().get(h).map(|(_, status)| status)
+    }
+
+    fn height(state: &TrustedState<TestCommit, TestHeader>) -> Height {
+        state.last_header().header().height()
+    }
+
+    fn error(result: Result<TrustedState<TestCommit, TestHeader>, Error>) -> Error {
+        match result {
+            Err(e) => e,
+            Ok(state) => panic!("verified height {}", height(&state)),
+        }
+    }
+
+    #[test]
+    fn an_honest_primary_is_trusted() {
+        let mut supervisor = new_supervisor(chain(LENGTH, EVERY), vec![chain(LENGTH, EVERY)]);
+        let verified = supervisor.verify_to_highest().unwrap();
+        assert_eq!(height(&verified), LENGTH);
+        assert!(status(&supervisor, LENGTH) == Some(Status::Trusted));
+        assert_eq!(supervisor.status().primary_replacements, 0);
+        assert_eq!(supervisor.status().witnesses, 1);
+        assert!(supervisor.submissions().is_empty());
+    }
+
+    #[test]
+    fn a_primary_which_went_away_is_replaced() {
+        let witnesses = vec![chain(LENGTH, EVERY), chain(LENGTH, EVERY)];
+        let mut supervisor = new_supervisor(MemoryProvider::new(), witnesses);
+        let verified = supervisor.verify_to_highest().unwrap();
+        assert_eq!(height(&verified), LENGTH);
+        assert!(status(&supervisor, LENGTH) == Some(Status::Trusted));
+        assert_eq!(supervisor.status().primary_replacements, 1);
+        assert_eq!(supervisor.status().witnesses, 1);
+
+        let mut supervisor = new_supervisor(MemoryProvider::new(), vec![]);
+        match error(supervisor.verify_to_target(LENGTH)).kind() {
+            Kind::NoWitnesses => (),
+            kind => panic!("unexpected error {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn a_faulty_primary_is_replaced() {
+        // the last header of the primary is signed by none of the validators
+        let primary = chain_ending_with(signed_by(LENGTH, EVERY, 1, vec![]));
+        let witnesses = vec![chain(LENGTH, EVERY), chain(LENGTH, EVERY)];
+        let mut supervisor = new_supervisor(primary, witnesses);
+        let verified = supervisor.verify_to_target(LENGTH).unwrap();
+        assert_eq!(verified.last_header().header().fork, 0);
+        assert!(status(&supervisor, LENGTH) == Some(Status::Trusted));
+        assert_eq!(supervisor.status().primary_replacements, 1);
+        assert_eq!(supervisor.status().witnesses, 1);
+    }
+
+    #[test]
+    fn a_faulty_witness_is_dropped() {
+        let faulty = chain_ending_with(signed_by(LENGTH, EVERY, 1, vec![]));
+        let witnesses = vec![faulty, chain(LENGTH, EVERY)];
+        let mut supervisor = new_supervisor(chain(LENGTH, EVERY), witnesses);
+        supervisor.verify_to_target(LENGTH).unwrap();
+        assert!(status(&supervisor, LENGTH) == Some(Status::Trusted));
+        assert_eq!(supervisor.status().primary_replacements, 0);
+        assert_eq!(supervisor.status().witnesses, 1);
+    }
+
+    #[test]
+    fn an_attack_fails_the_trace_and_is_reported() {
+        // the validators signed another header at the last height
+        let witnesses = vec![chain_ending_with(forked_header(LENGTH, EVERY, 1))];
+        let mut supervisor = new_supervisor(chain(LENGTH, EVERY), witnesses);
+        match error(supervisor.verify_to_target(LENGTH)).kind() {
+            Kind::ConflictingHeaders { height } => assert_eq!(*height, LENGTH),
+            kind => panic!("unexpected error {:?}", kind),
+        }
+        assert!(status(&supervisor, LENGTH) == Some(Status::Failed));
+        assert!(status(&supervisor, 1) == Some(Status::Trusted));
+        assert_eq!(supervisor.submissions().len(), 2);
+        assert!(supervisor.submissions().iter().all(|s| s.result().is_ok()));
+        assert_eq!(supervisor.primary().reported_evidence(), 1);
+        assert_eq!(supervisor.witnesses()[0].reported_evidence(), 1);
+    }
+
+    #[test]
+    fn a_header_no_witness_served_is_not_trusted() {
+        for witnesses in vec![vec![], vec![MemoryProvider::new()]] {
+            let mut supervisor = new_supervisor(chain(LENGTH, EVERY), witnesses);
+            match error(supervisor.verify_to_target(LENGTH)).kind() {
+                Kind::NoWitnesses => (),
+                kind => panic!("unexpected error {:?}", kind),
+            }
+            assert!(status(&supervisor, LENGTH) == Some(Status::Verified));
+        }
+    }
+
+    #[test]
+    fn a_header_from_the_future_does_not_replace_the_primary() {
+        let witnesses = vec![chain(LENGTH + 5, EVERY)];
+        let mut supervisor = new_supervisor(chain(LENGTH + 5, EVERY), witnesses);
+        match error(supervisor.verify_to_target(LENGTH + 5)).kind() {
+            Kind::HeaderFromFuture { height } => assert_eq!(*height, LENGTH + 5),
+            kind => panic!("unexpected error {:?}", kind),
+        }
+        assert_eq!(supervisor.status().primary_replacements, 0);
+    }
+
+    #[test]
+    fn an_expired_light_client_is_reinitialized() {
+        let length = 2 * TRUSTING_PERIOD;
+        let checkpoint = length - 10;
+        let hash = signed_header(checkpoint, EVERY).header().hash();
+        let witnesses = vec![chain(length, EVERY)];
+        let mut supervisor = supervisor_at(length, chain(length, EVERY), witnesses);
+        assert!(supervisor.needs_reinitialization().unwrap());
+        match error(supervisor.verify_to_target(length)).kind() {
+            Kind::Expired { .. } => (),
+            kind => panic!("unexpected error {:?}", kind),
+        }
+
+        // a header of a fork which failed to verify does not prevent it
+        let failed = TrustedState::new(
+            &forked_header(checkpoint, EVERY, 1),
+            &validators(checkpoint + 1, EVERY),
+        );
+        supervisor.store.insert(failed, Status::Failed).unwrap();
+
+        let trusted = supervisor.reinitialize(checkpoint, hash).unwrap();
+        assert!(trusted.last_header().header().hash() == hash);
+        assert!(status(&supervisor, 1) == Some(Status::Archived));
+        assert!(status(&supervisor, checkpoint) == Some(Status::Trusted));
+        assert!(!supervisor.needs_reinitialization().unwrap());
+        match error(supervisor.reinitialize(checkpoint, hash)).kind() {
+            Kind::NotExpired => (),
+            kind => panic!("unexpected error {:?}", kind),
+        }
+
+        let verified = supervisor.verify_to_target(length).unwrap();
+        assert_eq!(height(&verified), length);
+    }
+
+    #[test]
+    fn reinitialization_keeps_a_verified_header_of_another_fork() {
+        let length = 2 * TRUSTING_PERIOD;
+        let checkpoint = length - 10;
+        let hash = signed_header(checkpoint, EVERY).header().hash();
+        let witnesses = vec![chain(length, EVERY)];
+        let mut supervisor = supervisor_at(length, chain(length, EVERY), witnesses);
+        let verified = TrustedState::new(
+            &forked_header(checkpoint, EVERY, 1),
+            &validators(checkpoint + 1, EVERY),
+        );
+        supervisor.store.insert(verified, Status::Verified).unwrap();
+
+        match error(supervisor.reinitialize(checkpoint, hash)).kind() {
+            Kind::ConflictingHeaders { height } => assert_eq!(*height, checkpoint),
+            kind => panic!("unexpected error {:?}", kind),
+        }
+        assert!(status(&supervisor, 1) == Some(Status::Trusted));
+        assert!(status(&supervisor, checkpoint) == Some(Status::Verified));
+    }
+}
@@ -0,0 +1,158 @@
+//! A synthetic chain to test the light client against.
+//!
+//! The chain has 10 validators of equal power, one of which is replaced
+//! every `every` heights: the validator set at height h is made of the
+//! validators numbered h / every to h / every + 9. Every header is signed
+//! by all the validators at its height, and its time is its height in
+//! seconds after the epoch.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use account;
+use hash::{Algorithm, Hash};
+use lite::error::Error;
+use lite::memory_provider::MemoryProvider;
+use lite::types::{Commit, Header, Height, SignedHeader, Validator, ValidatorSetImpl};
+
+/// Number of validators at every height.
+pub const SET_SIZE: u64 = 10;
+
+/// Voting power of every validator.
+pub const POWER: u64 = 10;
+
+/// A hash telling apart the given tag and number.
+pub fn hash(tag: u8, n: u64) -> Hash {
+    let mut bytes = [0u8; 32];
+    bytes[0] = tag;
+    bytes[1..9].copy_from_slice(&n.to_le_bytes());
+    Hash::new(Algorithm::Sha256, &bytes).unwrap()
+}
+
+/// The address of the validator numbered n.
+pub fn address(n: u64) -> account::Id {
+    let mut bytes = [0u8; account::LENGTH];
+    bytes[account::LENGTH - 8..].copy_from_slice(&n.to_be_bytes());
+    account::Id::new(bytes)
+}
+
+/// Number of the first validator of the set at height h.
+pub fn first_validator(h: Height, every: u64) -> u64 {
+    h / every
+}
+
+/// The validator set at height h.
+pub fn validators(h: Height, every: u64) -> ValidatorSetImpl {
+    let first = first_validator(h, every);
+    let validators = (first..first + SET_SIZE)
+        .map(|n| Validator::new(address(n), POWER))
+        .collect();
+    ValidatorSetImpl::new(hash(1, first), validators)
+}
+
+/// A header of the chain, or of a fork of it if `fork` is not 0.
+/// Forks only differ from the chain by their app hash, hence their hash.
+#[derive(Clone)]
+pub struct TestHeader {
+    pub height: Height,
+    pub every: u64,
+    pub fork: u8,
+}
+
+impl Header for TestHeader {
+    fn height(&self) -> Height {
+        self.height
+    }
+    fn bft_time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.height)
+    }
+    fn validators_hash(&self) -> Hash {
+        hash(1, first_validator(self.height, self.every))
+    }
+    fn next_validators_hash(&self) -> Hash {
+        hash(1, first_validator(self.height + 1, self.every))
+    }
+    fn consensus_hash(&self) -> Hash {
+        hash(2, 0)
+    }
+    fn app_hash(&self) -> Hash {
+        hash(3 + 16 * self.fork, self.height)
+    }
+    fn last_results_hash(&self) -> Hash {
+        hash(4, self.height)
+    }
+    fn hash(&self) -> Hash {
+        hash(5 + 16 * self.fork, self.height)
+    }
+}
+
+/// A commit for the header with the given hash. The signatures are not
+/// checked: only the addresses of the signers are kept.
+#[derive(Clone)]
+pub struct TestCommit {
+    pub header_hash: Hash,
+    pub signers: Vec<account::Id>,
+}
+
+impl Commit for TestCommit {
+    fn header_hash(&self) -> Hash {
+        self.header_hash
+    }
+    fn voting_power_in(&self, vals: &ValidatorSetImpl) -> Result<u64, Error> {
+        Ok(vals
+            .validators()
+            .iter()
+            .filter(|v| self.signers.contains(&v.address()))
+            .fold(0u64, |signed, v| signed.saturating_add(v.power())))
+    }
+    fn signers(&self) -> Vec<account::Id> {
+        self.signers.clone()
+    }
+    fn round(&self) -> u32 {
+        0
+    }
+    fn validate(&self, _vals: &ValidatorSetImpl) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// The header of the given fork at height h, signed by the given validators.
+pub fn signed_by(
+    h: Height,
+    every: u64,
+    fork: u8,
+    signers: Vec<account::Id>,
+) -> SignedHeader<TestCommit, TestHeader> {
+    let header = TestHeader {
+        height: h,
+        every,
+        fork,
+    };
+    let commit = TestCommit {
+        header_hash: header.hash(),
+        signers,
+    };
+    SignedHeader::new(commit, header)
+}
+
+/// The header of the given fork at height h, signed by all the validators at h.
+pub fn forked_header(h: Height, every: u64, fork: u8) -> SignedHeader<TestCommit, TestHeader> {
+    let signers = validators(h, every).validators().iter().map(|v| v.address()).collect();
+    signed_by(h, every, fork, signers)
+}
+
+/// The header of the chain at height h.
+pub fn signed_header(h: Height, every: u64) -> SignedHeader<TestCommit, TestHeader> {
+    forked_header(h, every, 0)
+}
+
+/// A provider serving the chain from height 1 to length.
+pub fn chain(length: Height, every: u64) -> MemoryProvider<TestCommit, TestHeader> {
+    let mut provider = MemoryProvider::new();
+    for h in 1..=length {
+        provider.add_signed_header(signed_header(h, every));
+    }
+    for h in 1..=length + 1 {
+        provider.add_validator_set(h, validators(h, every));
+    }
+    provider
+}
@@ -1,9 +1,12 @@
 //! All traits that are necessary and need to be implemented to use the main
 //! verification logic in [`super::verifier`] for a light client.
 
+use super::super::account;
 use super::super::hash::Hash;
 
 use lite::error::{Error, Kind};
+use lite::evidence::LightClientAttackEvidence;
+use std::str::FromStr;
 use std::time::SystemTime;
 
 pub type Height = u64;
@@ -22,6 +25,13 @@ pub trait Header: Clone {
     fn validators_hash(&self) -> Hash;
     fn next_validators_hash(&self) -> Hash;
 
+    /// Hash of the consensus parameters for this block.
+    fn consensus_hash(&self) -> Hash;
+    /// Hash of the application state after the previous block.
+    fn app_hash(&self) -> Hash;
+    /// Hash of the results of the transactions of the previous block.
+    fn last_results_hash(&self) -> Hash;
+
     /// Hash of the header (ie. the hash of the block).
     fn hash(&self) -> Hash;
 }
@@ -36,13 +46,56 @@ pub trait ValidatorSet/*: Clone*/ {
     fn total_power(&self) -> u64;
 }
 */
+/// Validator is a member of a validator set: its address and voting power.
+#[derive(Copy, Clone)]
+pub struct Validator {
+    address: account::Id,
+    power: u64,
+}
+
+impl Validator {
+    pub fn new(address: account::Id, power: u64) -> Self {
+        Self { address, power }
+    }
+
+    pub fn address(&self) -> account::Id {
+        self.address
+    }
+
+    #[pure]
+    pub fn power(&self) -> u64 {
+        self.power
+    }
+}
+
+/// Maximum total voting power of a validator set allowed by the protocol,
+/// ie. `i64::max_value() / 8` as in tendermint.
+/// Validator sets with more power are rejected by the verifier.
+pub const MAX_TOTAL_VOTING_POWER: u64 = 1_152_921_504_606_846_975;
+
 #[derive(Clone)]
 pub struct ValidatorSetImpl {
     hash: Hash,
     total_power: u64,
+    validators: Vec<Validator>,
 }
 
 impl ValidatorSetImpl {
+    /// Create the validator set with the given hash and validators.
+    /// The total power is the sum of the powers of the validators. It saturates
+    /// at `u64::max_value()`, which is above [`MAX_TOTAL_VOTING_POWER`].
+    #[trusted]
+    pub fn new(hash: Hash, validators: Vec<Validator>) -> Self {
+        let total_power = validators
+            .iter()
+            .fold(0u64, |total, v| total.saturating_add(v.power()));
+        Self {
+            hash,
+            total_power,
+            validators,
+        }
+    }
+
     /// Hash of the validator set.
     pub fn hash(&self) -> Hash {
         self.hash.clone()
@@ -53,6 +106,109 @@ impl ValidatorSetImpl {
     pub fn total_power(&self) -> u64 {
         self.total_power
     }
+
+    /// The members of the set.
+    pub fn validators(&self) -> &[Validator] {
+        &self.validators
+    }
+
+    /// The member of the set with the given address, if any.
+    #[trusted]
+    pub fn validator(&self, address: &account::Id) -> Option<&Validator> {
+        self.validators.iter().find(|v| v.address == *address)
+    }
+
+    /// Number of distinct members of the set among the given addresses.
+    #[trusted]
+    pub fn count_members(&self, addresses: &[account::Id]) -> usize {
+        self.validators
+            .iter()
+            .filter(|v| addresses.contains(&v.address))
+            .count()
+    }
+
+    /// How much of this trusted set is still present in the untrusted set:
+    /// its members which are also members of the untrusted set, by address,
+    /// and their voting power in both sets.
+    #[trusted]
+    pub fn overlap(&self, untrusted: &ValidatorSetImpl) -> OverlapReport {
+        let mut retained_validators = 0;
+        let mut retained_power = 0u64;
+        let mut untrusted_power_of_retained = 0u64;
+        for trusted_validator in &self.validators {
+            if let Some(untrusted_validator) = untrusted.validator(&trusted_validator.address) {
+                retained_validators += 1;
+                retained_power = retained_power.saturating_add(trusted_validator.power);
+                untrusted_power_of_retained =
+                    untrusted_power_of_retained.saturating_add(untrusted_validator.power);
+            }
+        }
+        OverlapReport {
+            trusted_total_power: self.total_power,
+            retained_power,
+            untrusted_total_power: untrusted.total_power,
+            untrusted_power_of_retained,
+            retained_validators,
+        }
+    }
+}
+
+/// OverlapReport tells how much of a trusted validator set is still present
+/// in an untrusted validator set, see [`ValidatorSetImpl::overlap`].
+#[derive(Copy, Clone)]
+pub struct OverlapReport {
+    trusted_total_power: u64,
+    retained_power: u64,
+    untrusted_total_power: u64,
+    untrusted_power_of_retained: u64,
+    retained_validators: usize,
+}
+
+impl OverlapReport {
+    /// Total voting power of the trusted set.
+    #[pure]
+    pub fn trusted_total_power(&self) -> u64 {
+        self.trusted_total_power
+    }
+
+    /// Voting power, in the trusted set, of its members still in the untrusted set.
+    #[pure]
+    pub fn retained_power(&self) -> u64 {
+        self.retained_power
+    }
+
+    /// Total voting power of the untrusted set.
+    #[pure]
+    pub fn untrusted_total_power(&self) -> u64 {
+        self.untrusted_total_power
+    }
+
+    /// Voting power, in the untrusted set, of the members of the trusted set.
+    #[pure]
+    pub fn untrusted_power_of_retained(&self) -> u64 {
+        self.untrusted_power_of_retained
+    }
+
+    /// Number of members of the trusted set still in the untrusted set.
+    #[pure]
+    pub fn retained_validators(&self) -> usize {
+        self.retained_validators
+    }
+
+    /// Whether a commit of the untrusted set may meet the trust threshold with respect
+    /// to the trusted set, ie. whether it would if all the retained validators signed it.
+    /// If not, no commit of the untrusted set can, which can be told without
+    /// verifying any commit. The untrusted set must first be checked against
+    /// its header, eg. with [`VerificationPredicates::validator_sets_match`].
+    ///
+    /// [`VerificationPredicates::validator_sets_match`]: super::predicates::VerificationPredicates::validator_sets_match
+    pub fn may_meet<L>(&self, trust_threshold: L) -> bool
+    where
+        L: TrustThreshold,
+    {
+        trust_threshold.is_enough_power(self.retained_power, self.trusted_total_power)
+            && trust_threshold.is_enough_signers(self.retained_validators)
+    }
 }
 
 
@@ -82,18 +238,48 @@ pub trait Commit: Clone {
     /// can be cross-referenced with the given `vals`.
     fn voting_power_in(&self, vals: &ValidatorSetImpl) -> Result<u64, Error>;
 
+    /// Addresses of the validators whose signature is included in the commit,
+    /// ie. `signers(h.Commit)` in the spec.
+    fn signers(&self) -> Vec<account::Id>;
+
+    /// The consensus round in which the commit was made.
+    fn round(&self) -> u32;
+
     /// Implementers should add addition validation against the given validator set
     /// or other implementation specific validation here.
     /// E.g. validate that the length of the included signatures in the commit match
     /// with the number of validators.
+    /// Failures specific to the implementation can be reported with
+    /// `Kind::ImplementationSpecific.context(reason)`.
     fn validate(&self, vals: &ValidatorSetImpl) -> Result<(), Error>;
 }
 
 /// TrustThreshold defines how much of the total voting power of a known
 /// and trusted validator set is sufficient for a commit to be
 /// accepted going forward.
+/// Implementations must never be weaker than 1/3 of the total voting power, as
+/// [`TrustThresholdFraction`] ensures: otherwise no honest validator may have signed.
 pub trait TrustThreshold: Copy + Clone /*+ Debug*/ {
     fn is_enough_power(&self, signed_voting_power: u64, total_voting_power: u64) -> bool;
+
+    /// Whether the threshold is not weaker than 1/3, ie. whether it never
+    /// accepts a third of the total voting power.
+    #[pure]
+    fn rejects_a_third(&self) -> bool;
+
+    /// Whether the given number of distinct trusted validators which signed is enough.
+    /// Thresholds only based on voting power accept any number.
+    fn is_enough_signers(&self, _signers: usize) -> bool {
+        true
+    }
+}
+
+#[pure]
+fn is_ok<T>(r: &Result<T, Error>) -> bool {
+    match r {
+        Ok(_) => true,
+        Err(_) => false,
+    }
 }
 
 /// TrustThresholdFraction defines what fraction of the total voting power of a known
@@ -113,15 +299,10 @@ pub struct TrustThresholdFraction {
 impl TrustThresholdFraction {
     #[pure]
     fn threshold_ok(numerator: u64, denominator: u64) -> bool {
-        return numerator <= denominator && denominator > 0 && 3 * numerator >= denominator;
-    }
-
-    #[pure]
-    fn is_ok(r: &Result<TrustThresholdFraction, Error>) -> bool {
-        match r {
-            Ok(_) => true,
-            Err(_) => false,
-        }
+        // in u128, so that 3 * numerator cannot overflow
+        return numerator <= denominator
+            && denominator > 0
+            && 3 * (numerator as u128) >= denominator as u128;
     }
 
     /// Instantiate a TrustThresholdFraction if the given denominator and
@@ -129,7 +310,7 @@ impl TrustThresholdFraction {
     ///
     /// The parameters are valid iff `1/3 <= numerator/denominator <= 1`.
     /// In any other case we return [`Error::InvalidTrustThreshold`].
-    #[ensures="Self::threshold_ok(numerator, denominator) ==> Self::is_ok(&result)"]
+    #[ensures="Self::threshold_ok(numerator, denominator) ==> is_ok(&result)"]
     pub fn new(numerator: u64, denominator: u64) -> Result<Self, Error> {
         if Self::threshold_ok(numerator, denominator) {
             return Ok(Self {
@@ -137,15 +318,58 @@ impl TrustThresholdFraction {
                 denominator,
             });
         } else {
-            Err(Kind::InvalidTrustThreshold)
+            Err(Kind::InvalidTrustThreshold.into())
         }
     }
+
+    #[pure]
+    pub fn numerator(&self) -> u64 {
+        self.numerator
+    }
+
+    #[pure]
+    pub fn denominator(&self) -> u64 {
+        self.denominator
+    }
+}
+
+/// Parse a fraction written as `numerator/denominator`, eg. `"2/3"`.
+/// Returns [`Kind::InvalidTrustThreshold`] if it is malformed or not valid,
+/// see [`TrustThresholdFraction::new`].
+impl FromStr for TrustThresholdFraction {
+    type Err = Error;
+
+    #[trusted]
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut parts = s.splitn(2, '/');
+        let (numerator, denominator) = match (parts.next(), parts.next()) {
+            (Some(numerator), Some(denominator)) => (numerator.trim(), denominator.trim()),
+            _ => {
+                return Err(Kind::InvalidTrustThreshold
+                    .context(format!("expected numerator/denominator, got {:?}", s)))
+            }
+        };
+        let numerator = numerator
+            .parse()
+            .map_err(|e| Kind::InvalidTrustThreshold.context(e))?;
+        let denominator = denominator
+            .parse()
+            .map_err(|e| Kind::InvalidTrustThreshold.context(e))?;
+        Self::new(numerator, denominator)
+    }
 }
 
 // TODO: should this go in the central place all impls live instead? (currently lite_impl)
 impl TrustThreshold for TrustThresholdFraction {
+    /// The products are computed in u128: the product of two u64 cannot overflow it.
     fn is_enough_power(&self, signed_voting_power: u64, total_voting_power: u64) -> bool {
-        signed_voting_power * self.denominator > total_voting_power * self.numerator
+        (signed_voting_power as u128) * (self.denominator as u128)
+            > (total_voting_power as u128) * (self.numerator as u128)
+    }
+
+    #[pure]
+    fn rejects_a_third(&self) -> bool {
+        Self::threshold_ok(self.numerator, self.denominator)
     }
 }
 
@@ -158,8 +382,160 @@ impl Default for TrustThresholdFraction {
     }
 }
 
+/// TrustThresholdMinSigners requires a minimum number of distinct trusted validators
+/// to sign, on top of a fraction of the trusted voting power, so that a few validators
+/// holding most of the power are not enough.
+#[derive(Copy, Clone)]
+pub struct TrustThresholdMinSigners {
+    min_signers: usize,
+    fraction: TrustThresholdFraction,
+}
+
+impl TrustThresholdMinSigners {
+    /// Instantiate a TrustThresholdMinSigners if the fraction is valid, ie. at least 1/3,
+    /// see [`TrustThresholdFraction::new`].
+    #[ensures="TrustThresholdFraction::threshold_ok(numerator, denominator) ==> is_ok(&result)"]
+    pub fn new(min_signers: usize, numerator: u64, denominator: u64) -> Result<Self, Error> {
+        let fraction = TrustThresholdFraction::new(numerator, denominator)?;
+        Ok(Self {
+            min_signers,
+            fraction,
+        })
+    }
+
+    pub fn min_signers(&self) -> usize {
+        self.min_signers
+    }
+
+    pub fn fraction(&self) -> TrustThresholdFraction {
+        self.fraction
+    }
+}
+
+impl TrustThreshold for TrustThresholdMinSigners {
+    fn is_enough_power(&self, signed_voting_power: u64, total_voting_power: u64) -> bool {
+        self.fraction.is_enough_power(signed_voting_power, total_voting_power)
+    }
+
+    /// The minimum number of signers does not make up for a weak fraction.
+    #[pure]
+    fn rejects_a_third(&self) -> bool {
+        self.fraction.rejects_a_third()
+    }
+
+    fn is_enough_signers(&self, signers: usize) -> bool {
+        signers >= self.min_signers
+    }
+}
+
+/// TrustThresholdPowerFloor requires the trusted validators which signed to hold
+/// at least a given absolute voting power, on top of a fraction of the trusted
+/// voting power, so that the threshold stays meaningful for small validator sets.
+#[derive(Copy, Clone)]
+pub struct TrustThresholdPowerFloor {
+    min_power: u64,
+    fraction: TrustThresholdFraction,
+}
+
+impl TrustThresholdPowerFloor {
+    /// Instantiate a TrustThresholdPowerFloor if the fraction is valid, ie. at least 1/3,
+    /// see [`TrustThresholdFraction::new`].
+    #[ensures="TrustThresholdFraction::threshold_ok(numerator, denominator) ==> is_ok(&result)"]
+    pub fn new(min_power: u64, numerator: u64, denominator: u64) -> Result<Self, Error> {
+        let fraction = TrustThresholdFraction::new(numerator, denominator)?;
+        Ok(Self {
+            min_power,
+            fraction,
+        })
+    }
+
+    pub fn min_power(&self) -> u64 {
+        self.min_power
+    }
+
+    pub fn fraction(&self) -> TrustThresholdFraction {
+        self.fraction
+    }
+}
+
+impl TrustThreshold for TrustThresholdPowerFloor {
+    fn is_enough_power(&self, signed_voting_power: u64, total_voting_power: u64) -> bool {
+        signed_voting_power >= self.min_power
+            && self.fraction.is_enough_power(signed_voting_power, total_voting_power)
+    }
+
+    /// The power floor does not make up for a weak fraction.
+    #[pure]
+    fn rejects_a_third(&self) -> bool {
+        self.fraction.rejects_a_third()
+    }
+}
+
+/// TrustThresholdAllOf is met iff both of the given thresholds are met.
+/// Combinations can be nested to require more than two thresholds.
+#[derive(Copy, Clone)]
+pub struct TrustThresholdAllOf<A, B>
+where
+    A: TrustThreshold,
+    B: TrustThreshold,
+{
+    first: A,
+    second: B,
+}
+
+impl<A, B> TrustThresholdAllOf<A, B>
+where
+    A: TrustThreshold,
+    B: TrustThreshold,
+{
+    /// Combine the given thresholds if their combination is not weaker than 1/3,
+    /// ie. if either of them is not, see [`TrustThreshold::rejects_a_third`].
+    /// Returns [`Kind::InvalidTrustThreshold`] otherwise.
+    #[ensures="(first.rejects_a_third() || second.rejects_a_third()) ==> is_ok(&result)"]
+    pub fn new(first: A, second: B) -> Result<Self, Error> {
+        if !first.rejects_a_third() && !second.rejects_a_third() {
+            return Err(Kind::InvalidTrustThreshold
+                .context("neither threshold rejects a third of the voting power"));
+        }
+        Ok(Self { first, second })
+    }
+
+    pub fn first(&self) -> A {
+        self.first
+    }
+
+    pub fn second(&self) -> B {
+        self.second
+    }
+}
+
+impl<A, B> TrustThreshold for TrustThresholdAllOf<A, B>
+where
+    A: TrustThreshold,
+    B: TrustThreshold,
+{
+    fn is_enough_power(&self, signed_voting_power: u64, total_voting_power: u64) -> bool {
+        self.first.is_enough_power(signed_voting_power, total_voting_power)
+            && self.second.is_enough_power(signed_voting_power, total_voting_power)
+    }
+
+    /// Both thresholds must be met, so it is enough that either is not weaker than 1/3.
+    #[pure]
+    fn rejects_a_third(&self) -> bool {
+        self.first.rejects_a_third() || self.second.rejects_a_third()
+    }
+
+    fn is_enough_signers(&self, signers: usize) -> bool {
+        self.first.is_enough_signers(signers) && self.second.is_enough_signers(signers)
+    }
+}
+
 /// Requester can be used to request [`SignedHeader`]s and [`ValidatorSet`]s for a
 /// given height, e.g., by talking to a tendermint fullnode through RPC.
+/// Failed requests should be reported as [`Kind::RequestFailed`], with the
+/// underlying error attached using [`Kind::context`]. The cryptographic and
+/// parsing errors of the crate, eg. of [`Hash::new`], convert into
+/// [`Kind::Crypto`] errors with `?`.
 pub trait Requester<C, H>
 where
     C: Commit,
@@ -170,6 +546,29 @@ where
 
     /// Request the validator set at height h.
     fn validator_set(&self, h: Height) -> Result<ValidatorSetImpl, Error>;
+
+    /// Request the height of the latest block known to the provider.
+    fn latest_height(&self) -> Result<Height, Error>;
+}
+
+/// EvidenceReporter extends [`Requester`] with the ability to submit
+/// evidence of misbehaviour to the provider, e.g., by broadcasting it
+/// to a tendermint fullnode through RPC.
+pub trait EvidenceReporter<C, H>: Requester<C, H>
+where
+    C: Commit,
+    H: Header,
+{
+    /// Submit the given evidence, returning its hash.
+    fn report_evidence(&self, evidence: &LightClientAttackEvidence<C, H>) -> Result<Hash, Error>;
+}
+
+/// Identifies one of the providers the light client talks to.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Provider {
+    Primary,
+    /// The witness at the given index.
+    Witness(usize),
 }
 
 
@@ -238,4 +637,162 @@ where
     pub fn header(&self) -> &H {
         &self.header
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use lite::error::Kind;
+    use lite::test_utils::{address, hash};
+
+    use super::{
+        TrustThreshold, TrustThresholdAllOf, TrustThresholdFraction, TrustThresholdMinSigners,
+        TrustThresholdPowerFloor, Validator, ValidatorSetImpl, MAX_TOTAL_VOTING_POWER,
+    };
+
+    // A threshold accepting any voting power.
+    #[derive(Copy, Clone)]
+    struct AnyPower;
+
+    impl TrustThreshold for AnyPower {
+        fn is_enough_power(&self, _signed_voting_power: u64, _total_voting_power: u64) -> bool {
+            true
+        }
+
+        fn rejects_a_third(&self) -> bool {
+            false
+        }
+    }
+
+    fn parse(s: &str) -> Option<(u64, u64)> {
+        match TrustThresholdFraction::from_str(s) {
+            Ok(fraction) => Some((fraction.numerator(), fraction.denominator())),
+            Err(e) => match e.kind() {
+                Kind::InvalidTrustThreshold => None,
+                kind => panic!("unexpected error {:?}", kind),
+            },
+        }
+    }
+
+    #[test]
+    fn fractions_are_parsed_and_validated() {
+        assert_eq!(parse("2/3"), Some((2, 3)));
+        assert_eq!(parse("1/3"), Some((1, 3)));
+        assert_eq!(parse(" 2 / 3 "), Some((2, 3)));
+        assert_eq!(parse("1/4"), None);
+        assert_eq!(parse("0/0"), None);
+        assert_eq!(parse("3/2"), None);
+        assert_eq!(parse("2"), None);
+        assert_eq!(parse("2/3/4"), None);
+        assert_eq!(parse("-1/3"), None);
+        assert_eq!(parse("18446744073709551616/18446744073709551616"), None);
+        assert_eq!(
+            parse("18446744073709551615/18446744073709551615"),
+            Some((u64::max_value(), u64::max_value()))
+        );
+    }
+
+    #[test]
+    fn every_policy_rejects_a_third() {
+        assert!(TrustThresholdFraction::default().rejects_a_third());
+        assert!(TrustThresholdMinSigners::new(5, 1, 3).unwrap().rejects_a_third());
+        assert!(TrustThresholdMinSigners::new(5, 1, 4).is_err());
+        assert!(TrustThresholdPowerFloor::new(100, 2, 3).unwrap().rejects_a_third());
+        assert!(TrustThresholdPowerFloor::new(100, 1, 4).is_err());
+
+        let fraction = TrustThresholdFraction::default();
+        let both = TrustThresholdAllOf::new(AnyPower, fraction).unwrap();
+        assert!(both.rejects_a_third());
+        assert!(!both.is_enough_power(1, 3));
+        assert!(TrustThresholdAllOf::new(fraction, AnyPower).is_ok());
+        match TrustThresholdAllOf::new(AnyPower, AnyPower) {
+            Err(e) => match e.kind() {
+                Kind::InvalidTrustThreshold => (),
+                kind => panic!("unexpected error {:?}", kind),
+            },
+            Ok(_) => panic!("combined two thresholds weaker than 1/3"),
+        }
+    }
+
+    #[test]
+    fn is_enough_power_near_the_maximum_total_power() {
+        let third = MAX_TOTAL_VOTING_POWER / 3;
+        assert_eq!(third * 3, MAX_TOTAL_VOTING_POWER);
+
+        let one_third = TrustThresholdFraction::default();
+        assert!(!one_third.is_enough_power(third, MAX_TOTAL_VOTING_POWER));
+        assert!(one_third.is_enough_power(third + 1, MAX_TOTAL_VOTING_POWER));
+
+        let two_thirds = TrustThresholdFraction::new(2, 3).unwrap();
+        assert!(!two_thirds.is_enough_power(2 * third, MAX_TOTAL_VOTING_POWER));
+        assert!(two_thirds.is_enough_power(2 * third + 1, MAX_TOTAL_VOTING_POWER));
+
+        // the products do not overflow even above the maximum
+        let all = TrustThresholdFraction::new(u64::max_value(), u64::max_value()).unwrap();
+        assert!(!all.is_enough_power(u64::max_value(), u64::max_value()));
+        assert!(one_third.is_enough_power(u64::max_value(), u64::max_value()));
+    }
+
+    // The set of the validators numbered n, with power p, for each (n, p).
+    fn set(validators: &[(u64, u64)]) -> ValidatorSetImpl {
+        let validators = validators
+            .iter()
+            .map(|&(n, power)| Validator::new(address(n), power))
+            .collect();
+        ValidatorSetImpl::new(hash(1, 0), validators)
+    }
+
+    #[test]
+    fn overlap_of_disjoint_and_identical_sets() {
+        let trusted = set(&[(0, 10), (1, 10), (2, 10)]);
+        let one_third = TrustThresholdFraction::default();
+
+        let disjoint = trusted.overlap(&set(&[(3, 10), (4, 10), (5, 10)]));
+        assert_eq!(disjoint.trusted_total_power(), 30);
+        assert_eq!(disjoint.retained_power(), 0);
+        assert_eq!(disjoint.untrusted_total_power(), 30);
+        assert_eq!(disjoint.untrusted_power_of_retained(), 0);
+        assert_eq!(disjoint.retained_validators(), 0);
+        assert!(!disjoint.may_meet(one_third));
+
+        let identical = trusted.overlap(&trusted);
+        assert_eq!(identical.retained_power(), 30);
+        assert_eq!(identical.untrusted_power_of_retained(), 30);
+        assert_eq!(identical.retained_validators(), 3);
+        assert!(identical.may_meet(one_third));
+        assert!(!identical.may_meet(TrustThresholdFraction::new(1, 1).unwrap()));
+    }
+
+    #[test]
+    fn overlap_counts_the_trusted_power_of_validators_whose_power_changed() {
+        let trusted = set(&[(0, 10), (1, 10), (2, 10)]);
+        let overlap = trusted.overlap(&set(&[(0, 100), (3, 10), (4, 10)]));
+        assert_eq!(overlap.retained_power(), 10);
+        assert_eq!(overlap.untrusted_total_power(), 120);
+        assert_eq!(overlap.untrusted_power_of_retained(), 100);
+        assert_eq!(overlap.retained_validators(), 1);
+        // the power gained in the untrusted set does not count towards the threshold
+        assert!(!overlap.may_meet(TrustThresholdFraction::default()));
+    }
+
+    #[test]
+    fn overlap_at_the_threshold() {
+        let trusted = set(&[(0, 1), (1, 1), (2, 1)]);
+        let one_third = TrustThresholdFraction::default();
+        let two_thirds = TrustThresholdFraction::new(2, 3).unwrap();
+
+        // exactly a third of the trusted power is retained
+        let third = trusted.overlap(&set(&[(0, 1), (3, 1), (4, 1)]));
+        assert!(!third.may_meet(one_third));
+
+        let two = trusted.overlap(&set(&[(0, 1), (1, 1), (3, 1)]));
+        assert!(two.may_meet(one_third));
+        assert!(!two.may_meet(two_thirds));
+
+        // enough power, but held by too few validators
+        let trusted = set(&[(0, 10), (1, 1), (2, 1)]);
+        let overlap = trusted.overlap(&set(&[(0, 10), (3, 1), (4, 1)]));
+        assert!(overlap.may_meet(one_third));
+        assert!(!overlap.may_meet(TrustThresholdMinSigners::new(2, 1, 3).unwrap()));
+    }
+}
@@ -1,5 +1,14 @@
 //! Main verification functions that can be used to implement a light client.
 //!
+//! None of the functions of this module is `#[trusted]`, so that Prusti
+//! verifies their bodies instead of assuming them. Keep it that way: use
+//! checked arithmetic, and no closures, which Prusti does not support.
+//!
+//! This only covers the code of this module. What it calls is assumed correct
+//! rather than verified: the `#[trusted]` functions of the other modules, eg.
+//! [`ValidatorSetImpl::overlap`] or [`ChurnHistory`], the [`Requester`],
+//! [`LightStore`] and [`Clock`] implementations, and the errors built with
+//! [`Kind::context`].
 //!
 //! # Examples
 //!
@@ -9,15 +18,63 @@
 //! ```
 
 use std::cmp::Ordering;
-use std::ops::Add;
 use std::time::{Duration, SystemTime};
 
+use hash::Hash;
+use lite::churn::{ChurnHistory, DEFAULT_CHURN_HISTORY_LEN};
+use lite::clock::Clock;
 use lite::error::{Error, Kind};
-use lite::types::{Commit, Header, Height, Requester, SignedHeader, TrustedState, TrustThreshold, ValidatorSetImpl};
+use lite::options::{Options, VerificationMode};
+use lite::store::{LightStore, Status};
+use lite::predicates::VerificationPredicates;
+use lite::types::{
+    Commit, Header, Height, OverlapReport, Requester, SignedHeader, TrustedState,
+    TrustThreshold, ValidatorSetImpl,
+};
+
+/// The voting power which signed a commit, out of the total voting power
+/// of the validator set it was checked against, and the number of distinct
+/// members of that set which signed.
+#[derive(Copy, Clone)]
+pub struct VotingPowerTally {
+    pub total: u64,
+    pub signed: u64,
+    pub signers: usize,
+}
+
+/// Outcome of the verification of a single untrusted header against a trusted state,
+/// as described by the light client spec.
+pub enum Verdict {
+    /// The untrusted header verifies and can be trusted.
+    Success,
+    /// The untrusted header is valid, but not enough of the trusted validators
+    /// signed it to skip to it. Verifying an intermediate height first might help.
+    NotEnoughTrust(VotingPowerTally),
+    /// The untrusted header, or the data it came with, is invalid.
+    Invalid(Error),
+}
+
+impl Verdict {
+    /// Turn the verdict into a result, [`Verdict::NotEnoughTrust`] being
+    /// reported as [`Kind::InsufficientVotingPower`].
+    pub fn into_result(self) -> Result<(), Error> {
+        match self {
+            Verdict::Success => Ok(()),
+            Verdict::NotEnoughTrust(tally) => Err(Kind::InsufficientVotingPower {
+                total: tally.total,
+                signed: tally.signed,
+            }.into()),
+            Verdict::Invalid(e) => Err(e),
+        }
+    }
+}
 
 /// Returns an error if the header has expired according to the given
-/// trusting_period and current time. If so, the verifier must be reset subjectively.
-fn is_within_trust_period<H>(
+/// trusting_period and current time. If so, the verifier must be reset subjectively,
+/// see [`super::supervisor::Supervisor::reinitialize`].
+/// Returns [`Kind::ExpirationOutOfRange`] if the expiration time of the header
+/// cannot be represented, eg. for a huge trusting period.
+pub fn is_within_trust_period<H>(
     last_header: &H,
     trusting_period: Duration,
     now: SystemTime,
@@ -26,111 +83,89 @@ fn is_within_trust_period<H>(
         H: Header,
 {
     let header_time: SystemTime = last_header.bft_time();
-    let expires_at = header_time.add(trusting_period);
+    let expires_at = match header_time.checked_add(trusting_period) {
+        Some(expires_at) => expires_at,
+        None => {
+            return Err(Kind::ExpirationOutOfRange {
+                header_time,
+                trusting_period,
+            }.into())
+        }
+    };
     // Ensure now > expires_at.
     if expires_at <= now {
         return Err(Kind::Expired {
             at: expires_at,
             now,
-        });
+        }.into());
     }
     // Also make sure the header is not after now.
     if header_time > now {
-        Err(Kind::DurationOutOfRange)
+        Err(Kind::DurationOutOfRange.into())
     } else {
         Ok(())
     }
 }
 
+/// Returns an error if the header's time is too far in the future of now,
+/// which means that either our local clock is really slow or the blockchain's
+/// BFT time is really wrong.
+fn is_not_from_future<H>(
+    header: &H,
+    max_clock_drift: Duration,
+    now: SystemTime,
+) -> Result<(), Error>
+    where
+        H: Header,
+{
+    // if now + max_clock_drift is not representable, no header can be after it
+    if let Some(latest_allowed) = now.checked_add(max_clock_drift) {
+        if header.bft_time() > latest_allowed {
+            return Err(Kind::HeaderFromFuture {
+                height: header.height(),
+            }.into());
+        }
+    }
+    Ok(())
+}
+
+// Record the height being verified on the error of the given result, if any.
+fn at_height<T>(result: Result<T, Error>, height: Height) -> Result<T, Error> {
+    match result {
+        Ok(t) => Ok(t),
+        Err(e) => Err(e.at_height(height)),
+    }
+}
+
 /// Validate the validators, next validators, against the signed header.
 /// This is equivalent to validateSignedHeaderAndVals in the spec.
-fn validate<C, H>(
+fn validate<C, H, V>(
     signed_header: &SignedHeader<C, H>,
     vals: &ValidatorSetImpl,
     next_vals: &ValidatorSetImpl,
+    predicates: &V,
 ) -> Result<(), Error>
     where
         C: Commit,
         H: Header,
+        V: VerificationPredicates,
 {
     let header = signed_header.header();
     let commit = signed_header.commit();
 
+    // ensure the voting power arithmetic on the validators cannot overflow
+    predicates.voting_power_within_bounds(vals)?;
+    predicates.voting_power_within_bounds(next_vals)?;
+
     // ensure the header validator hashes match the given validators
-    if header.validators_hash() != vals.hash() {
-        return Err(Kind::InvalidValidatorSet {
-            // header_val_hash: header.validators_hash(),
-            // val_hash: vals.hash(),
-        }.into());
-    }
-    if header.next_validators_hash() != next_vals.hash() {
-        return Err(Kind::InvalidNextValidatorSet {
-            // header_next_val_hash: header.next_validators_hash(),
-            // next_val_hash: next_vals.hash(),
-        }.into());
-    }
+    predicates.validator_sets_match(header, vals)?;
+    predicates.next_validators_match(header, next_vals)?;
 
     // ensure the header matches the commit
-    if header.hash() != commit.header_hash() {
-        return Err(Kind::InvalidCommitValue {
-            // header_hash: header.hash(),
-            // commit_hash: commit.header_hash(),
-        }.into());
-    }
+    predicates.header_matches_commit(header, commit)?;
 
     // additional implementation specific validation:
-    commit.validate(vals)?;
-    Ok(())
-}
-
-/// Verify that +2/3 of the correct validator set signed this commit.
-/// NOTE: These validators are expected to be the correct validators for the commit,
-/// but since we're using voting_power_in, we can't actually detect if there's
-/// votes from validators not in the set.
-fn verify_commit_full<C>(vals: &ValidatorSetImpl, commit: &C) -> Result<(), Error>
-    where
-        C: Commit,
-{
-    let total_power = vals.total_power();
-    let signed_power = commit.voting_power_in(vals)?;
-
-    // check the signers account for +2/3 of the voting power
-    if signed_power * 3 <= total_power * 2 {
-        return Err(Kind::InvalidCommit {
-            total: total_power,
-            signed: signed_power,
-        }.into());
-    }
-
-    Ok(())
-}
-
-/// Verify that +1/3 of the given validator set signed this commit.
-/// NOTE the given validators do not necessarily correspond to the validator set for this commit,
-/// but there may be some intersection. The trust_level parameter allows clients to require more
-/// than +1/3 by implementing the TrustLevel trait accordingly.
-fn verify_commit_trusting<C, L>(
-    validators: &ValidatorSetImpl,
-    commit: &C,
-    trust_level: L,
-) -> Result<(), Error>
-    where
-        C: Commit,
-        L: TrustThreshold,
-{
-    let total_power = validators.total_power();
-    let signed_power = commit.voting_power_in(validators)?;
-
-    // TODO: can add invariant in trait
-    // check the signers account for +1/3 of the voting power (or more if the
-    // trust_level requires so)
-    if !trust_level.is_enough_power(signed_power, total_power) {
-        return Err(Kind::InsufficientVotingPower {
-            total: total_power,
-            signed: signed_power,
-        }.into());
-    }
-
+    predicates.valid_commit(commit, vals)?;
     Ok(())
 }
 
@@ -138,67 +173,127 @@ fn verify_commit_trusting<C, L>(
 // Includes all validation and signature verification.
 // Not publicly exposed since it does not check for expiry
 // and hence it's possible to use it incorrectly.
-// If trusted_state is not expired and this returns Ok, the
+// If trusted_state is not expired and this returns Verdict::Success, the
 // untrusted_sh and untrusted_next_vals can be considered trusted.
-fn verify_single_inner<H, C, L>(
+// Verdict::NotEnoughTrust is only returned for non-adjacent headers
+// which are otherwise valid.
+fn verify_single_inner<H, C, L, V>(
     trusted_state: &TrustedState<C, H>,
     untrusted_sh: &SignedHeader<C, H>,
     untrusted_vals: &ValidatorSetImpl,
     untrusted_next_vals: &ValidatorSetImpl,
-    trust_threshold: L,
-) -> Result<(), Error>
+    options: &Options<L, V>,
+) -> Verdict
     where
         H: Header,
         C: Commit,
         L: TrustThreshold,
+        V: VerificationPredicates,
 {
+    let predicates = options.predicates();
+
     // validate the untrusted header against its commit, vals, and next_vals
     let untrusted_header = untrusted_sh.header();
     let untrusted_commit = untrusted_sh.commit();
 
-    validate(untrusted_sh, untrusted_vals, untrusted_next_vals)?;
+    if let Err(e) = validate(untrusted_sh, untrusted_vals, untrusted_next_vals, predicates) {
+        return Verdict::Invalid(e);
+    }
 
     // ensure the new height is higher.
     // if its +1, ensure the vals are correct.
     // if its >+1, ensure we can skip to it
     let trusted_header = trusted_state.last_header().header();
     let trusted_height = trusted_header.height();
-    let untrusted_height = untrusted_sh.header().height();
+    let untrusted_height = untrusted_header.height();
 
     // ensure the untrusted_header.bft_time() > trusted_header.bft_time()
-    if untrusted_header.bft_time() <= trusted_header.bft_time() {
-        return Err(Kind::NonIncreasingTime.into());
+    if let Err(e) = predicates.is_monotonic_bft_time(untrusted_header, trusted_header) {
+        return Verdict::Invalid(e);
+    }
+    if let Err(e) = predicates.is_monotonic_height(untrusted_header, trusted_header) {
+        return Verdict::Invalid(e);
     }
     let inc_trusted_height = match trusted_height.checked_add(1) {
         Some(inc_trusted_height) => inc_trusted_height,
-        None => return Err(Kind::ImplementationSpecific),
+        None => return Verdict::Invalid(Kind::ImplementationSpecific.context("height overflow")),
     };
-    // TODO: using untrusted_height.cmp(&inc_trusted_height) causes a crash
-    // match untrusted_height.cmp(&inc_trusted_height) ... ;
-    if untrusted_height < inc_trusted_height {
-        return Err(Kind::NonIncreasingHeight {
-            got: untrusted_height,
-            expected: trusted_height + 1,
-        }.into());
-    } else if untrusted_height == inc_trusted_height {
-        let trusted_vals_hash = trusted_header.next_validators_hash();
-        let untrusted_vals_hash = untrusted_header.validators_hash();
-        if trusted_vals_hash != untrusted_vals_hash {
-            // TODO: more specific error
-            // ie. differentiate from when next_vals.hash() doesnt
-            // match the header hash ...
-            return Err(Kind::InvalidNextValidatorSet {
-                // header_next_val_hash: trusted_vals_hash,
-                // next_val_hash: untrusted_vals_hash,
-            }.into());
+    if untrusted_height == inc_trusted_height {
+        if let Err(e) = predicates.valid_validator_set_link(untrusted_header, trusted_header) {
+            return Verdict::Invalid(e);
         }
     } else {
+        // check the signers account for +1/3 of the trusted voting power
+        // (or more if the trust threshold requires so)
         let trusted_vals = trusted_state.validators();
-        verify_commit_trusting(trusted_vals, untrusted_commit, trust_threshold)?;
+        match predicates.has_sufficient_validators_overlap(
+            untrusted_commit,
+            trusted_vals,
+            options.trust_threshold(),
+        ) {
+            Verdict::Success => (),
+            not_success => return not_success,
+        }
     }
 
     // All validation passed successfully. Verify the validators correctly committed the block.
-    verify_commit_full(untrusted_vals, untrusted_sh.commit())
+    match predicates.has_sufficient_signers_overlap(untrusted_commit, untrusted_vals) {
+        Ok(()) => Verdict::Success,
+        Err(e) => Verdict::Invalid(e),
+    }
+}
+
+impl<C, H> TrustedState<C, H>
+    where
+        H: Header,
+        C: Commit,
+{
+    /// Initialize the TrustedState subjectively, from a trusted height and header
+    /// hash obtained out of band, eg. configured by the operator.
+    ///
+    /// The signed header at the given height, its validators and next validators
+    /// are fetched with the requester. Returns an error if:
+    ///     - any requests fail
+    ///     - the header hash is not the configured one ([`Kind::TrustedHashMismatch`])
+    ///     - the validators do not match the header, see [`validate`]
+    ///     - the validators did not correctly commit the header ([`Kind::InvalidCommit`])
+    ///     - the header is not within the trusting period
+    ///       ([`Kind::Expired`] or [`Kind::DurationOutOfRange`])
+    ///
+    /// The trusting period and the predicates the validators are checked
+    /// with are the ones of the options.
+    pub fn init_from_options<R, L, V>(
+        height: Height,
+        hash: Hash,
+        req: &R,
+        now: SystemTime,
+        options: &Options<L, V>,
+    ) -> Result<Self, Error>
+        where
+            R: Requester<C, H>,
+            L: TrustThreshold,
+            V: VerificationPredicates,
+    {
+        let predicates = options.predicates();
+
+        let signed_header = req.signed_header(height)?;
+        if signed_header.header().hash() != hash {
+            return Err(Kind::TrustedHashMismatch { height }.into());
+        }
+
+        let inc_height = match height.checked_add(1) {
+            Some(inc_height) => inc_height,
+            None => return Err(Kind::ImplementationSpecific.context("height overflow")),
+        };
+        let vals = req.validator_set(height)?;
+        let next_vals = req.validator_set(inc_height)?;
+
+        validate(&signed_header, &vals, &next_vals, predicates)?;
+        predicates.has_sufficient_signers_overlap(signed_header.commit(), &vals)?;
+        is_within_trust_period(signed_header.header(), options.trusting_period(), now)?;
+
+        Ok(TrustedState::new(&signed_header, &next_vals))
+    }
 }
 
 /// Verify a single untrusted header against a trusted state.
@@ -210,43 +305,80 @@ fn verify_single_inner<H, C, L>(
 /// header to be trusted.
 ///
 /// This function is primarily for use by IBC handlers.
-pub fn verify_single<H, C, L>(
+/// See [`verify_single_verdict`] to tell a header which cannot be skipped
+/// to from an invalid one, and get the voting power tally.
+pub fn verify_single<H, C, L, V>(
     trusted_state: TrustedState<C, H>,
     untrusted_sh: &SignedHeader<C, H>,
     untrusted_vals: &ValidatorSetImpl,
     untrusted_next_vals: &ValidatorSetImpl,
-    trust_threshold: L,
-    trusting_period: Duration,
+    options: &Options<L, V>,
     now: SystemTime,
 ) -> Result<TrustedState<C, H>, Error>
     where
         H: Header,
         C: Commit,
         L: TrustThreshold,
+        V: VerificationPredicates,
 {
-    // Fetch the latest state and ensure it hasn't expired.
-    let trusted_sh = trusted_state.last_header();
-    is_within_trust_period(trusted_sh.header(), trusting_period, now)?;
-
-    verify_single_inner(
+    let verdict = verify_single_verdict(
         &trusted_state,
         untrusted_sh,
         untrusted_vals,
         untrusted_next_vals,
-        trust_threshold,
-    )?;
+        options,
+        now,
+    );
+    at_height(verdict.into_result(), untrusted_sh.header().height())?;
 
     // The untrusted header is now trusted;
     // return to the caller so they can update the store:
     Ok(TrustedState::new(untrusted_sh, untrusted_next_vals))
 }
 
+/// Verify a single untrusted header against a trusted state, like [`verify_single`],
+/// but return the [`Verdict`]: whether the header verifies, could not be skipped to
+/// because not enough trusted voting power signed it, or is invalid.
+/// An expired trusted state is reported as an invalid verdict.
+pub fn verify_single_verdict<H, C, L, V>(
+    trusted_state: &TrustedState<C, H>,
+    untrusted_sh: &SignedHeader<C, H>,
+    untrusted_vals: &ValidatorSetImpl,
+    untrusted_next_vals: &ValidatorSetImpl,
+    options: &Options<L, V>,
+    now: SystemTime,
+) -> Verdict
+    where
+        H: Header,
+        C: Commit,
+        L: TrustThreshold,
+        V: VerificationPredicates,
+{
+    // Fetch the latest state and ensure it hasn't expired.
+    let trusted_sh = trusted_state.last_header();
+    if let Err(e) = is_within_trust_period(trusted_sh.header(), options.trusting_period(), now) {
+        return Verdict::Invalid(e);
+    }
+    if let Err(e) = is_not_from_future(untrusted_sh.header(), options.max_clock_drift(), now) {
+        return Verdict::Invalid(e);
+    }
+
+    verify_single_inner(
+        trusted_state,
+        untrusted_sh,
+        untrusted_vals,
+        untrusted_next_vals,
+        options,
+    )
+}
+
 /// Attempt to "bisect" from the passed-in trusted state (with header of height h)
 /// to the given untrusted height (h+n) by requesting the necessary
 /// data (signed headers and validators from height (h, h+n]).
 ///
 /// On success, callers are responsible for storing the returned states
-/// which can now be trusted.
+/// which can now be trusted. See [`verify_bisection_in_store`] to have them
+/// written to a [`LightStore`] instead.
 ///
 /// Returns an error if:
 ///     - we're already at or past that height
@@ -260,86 +392,462 @@ pub fn verify_single<H, C, L>(
 /// Ensures our last trusted header hasn't expired yet, and that
 /// data from the untrusted height can be verified, possibly using
 /// data from intermediate heights.
+/// The clock is read again before accepting each intermediate state,
+/// so that no state is accepted once the state it is verified from expired.
+/// Before skipping to a height, the overlap of its validators, once checked against
+/// its header, with the trusted ones is checked (see [`ValidatorSetImpl::overlap`]):
+/// if no commit can meet the trust threshold, the commit is not even verified.
+/// The header is still fetched, together with its commit, as the [`Requester`]
+/// only serves signed headers: the pre-check saves the request for the next
+/// validators and the verification of the commit, not the commit download.
+/// In [`VerificationMode::Adaptive`], rather than trying the untrusted height
+/// first, each skip aims as far as the validator churn observed so far suggests
+/// the trust threshold allows, see [`ChurnHistory`]. The churn is only observed
+/// during this call: see [`verify_bisection_with_history`] to carry it across calls.
 ///
 /// This function is primarily for use by a light node.
-pub fn verify_bisection<C, H, L, R>(
+pub fn verify_bisection<C, H, L, V, R, K>(
     trusted_state: TrustedState<C, H>,
     untrusted_height: Height,
-    trust_threshold: L,
-    trusting_period: Duration,
-    now: SystemTime,
+    options: &Options<L, V>,
+    clock: &K,
     req: &R,
 ) -> Result<Vec<TrustedState<C, H>>, Error>
     where
         H: Header,
         C: Commit,
         L: TrustThreshold,
+        V: VerificationPredicates,
         R: Requester<C, H>,
+        K: Clock,
+{
+    let mut history = ChurnHistory::new(DEFAULT_CHURN_HISTORY_LEN);
+    verify_bisection_with_history(
+        trusted_state,
+        untrusted_height,
+        options,
+        clock,
+        req,
+        &mut history,
+    )
+}
+
+/// Bisect to the given untrusted height like [`verify_bisection`], recording
+/// the validator churn observed in [`VerificationMode::Adaptive`] into the given
+/// history, and picking the heights to skip to from what it already holds,
+/// eg. the churn observed by the previous syncs of a light client.
+/// The history is not used by the other modes.
+pub fn verify_bisection_with_history<C, H, L, V, R, K>(
+    trusted_state: TrustedState<C, H>,
+    untrusted_height: Height,
+    options: &Options<L, V>,
+    clock: &K,
+    req: &R,
+    history: &mut ChurnHistory,
+) -> Result<Vec<TrustedState<C, H>>, Error>
+    where
+        H: Header,
+        C: Commit,
+        L: TrustThreshold,
+        V: VerificationPredicates,
+        R: Requester<C, H>,
+        K: Clock,
 {
     // Ensure the latest state hasn't expired.
-    // Note we only check for expiry once in this
-    // verify_and_update_bisection function since we assume the
-    // time is passed in and we don't access a clock internally.
-    // Thus the trust_period must be long enough to incorporate the
-    // expected time to complete this function.
+    // Note the clock is read again before accepting each new state,
+    // to make sure the state it was verified from has not expired
+    // in the meantime: a slow sync fails instead of accepting it.
     let trusted_sh = trusted_state.last_header();
-    is_within_trust_period(trusted_sh.header(), trusting_period, now)?;
+    at_height(
+        is_within_trust_period(trusted_sh.header(), options.trusting_period(), clock.now()),
+        untrusted_height,
+    )?;
 
-    // TODO: consider fetching the header we're trying to sync to and
-    // checking that it's time is less then `now + X` for some small X.
+    // Every header we fetch is checked to be less than now + max_clock_drift.
     // If not, it means that either our local clock is really slow
     // or the blockchains BFT time is really wrong.
-    // In either case, we should probably raise an error.
-    // Note this would be stronger than checking that the untrusted
+    // Note this is stronger than checking that the untrusted
     // header is within the trusting period, as it could still diverge
-    // significantly from `now`.
-    // NOTE: we actually have to do this for every header we fetch,
-    // We do check bft_time is monotonic, but that check might happen too late.
-    // So every header we fetch must be checked to be less than now+X
+    // significantly from `now`. We do check bft_time is monotonic,
+    // but that check might happen too late.
 
     // this is only used to memoize intermediate trusted states:
     let mut cache: Vec<TrustedState<C, H>> = Vec::new();
-    // inner recursive function which assumes
+    // inner functions which assume
     // trusting_period check is already done.
-    verify_bisection_inner(
-        &trusted_state,
+    match options.mode() {
+        VerificationMode::Skipping => {
+            verify_bisection_inner(
+                &trusted_state,
+                untrusted_height,
+                options,
+                clock,
+                req,
+                &mut cache,
+            )?;
+        }
+        VerificationMode::Adaptive => {
+            verify_adaptive_inner(
+                &trusted_state,
+                untrusted_height,
+                options,
+                clock,
+                req,
+                &mut cache,
+                history,
+            )?;
+        }
+        VerificationMode::Sequential => {
+            verify_sequential_inner(
+                &trusted_state,
+                untrusted_height,
+                options,
+                clock,
+                req,
+                &mut cache,
+            )?;
+        }
+    }
+    // return all intermediate trusted states up to untrusted_height
+    Ok(cache)
+}
+
+/// Bisect to the given untrusted height, like [`verify_bisection`], starting
+/// from the highest trusted or verified state held by the store below that height.
+///
+/// All the intermediate states which are verified along the way, as well as
+/// the state at untrusted_height, are written to the store with the
+/// [`Status::Verified`] status. On success, the state at untrusted_height is returned.
+///
+/// Returns [`Kind::NoTrustedState`] if the store holds no state from which
+/// untrusted_height can be verified, and the errors of [`verify_bisection`] otherwise.
+pub fn verify_bisection_in_store<C, H, L, V, R, S, K>(
+    store: &mut S,
+    untrusted_height: Height,
+    options: &Options<L, V>,
+    clock: &K,
+    req: &R,
+) -> Result<TrustedState<C, H>, Error>
+    where
+        H: Header,
+        C: Commit,
+        L: TrustThreshold,
+        V: VerificationPredicates,
+        R: Requester<C, H>,
+        S: LightStore<C, H>,
+        K: Clock,
+{
+    let trusted_state = match store.highest_below(untrusted_height) {
+        Some(trusted_state) => trusted_state,
+        None => return Err(Kind::NoTrustedState {
+            height: untrusted_height,
+        }.into()),
+    };
+
+    let new_states = verify_bisection(
+        trusted_state,
         untrusted_height,
-        trust_threshold,
+        options,
+        clock,
         req,
-        &mut cache,
     )?;
-    // return all intermediate trusted states up to untrusted_height
-    Ok(cache)
+    store_verified(store, new_states)
 }
 
-// inner recursive function for verify_and_update_bisection.
-// see that function's docs.
-// A cache is passed in to memoize all new states to be trusted.
-// Note: we only write to the cache and it guarantees that we do
-// not store states twice.
-// Additionally, a new state is returned for convenience s.t. it can
-// be used for the other half of the recursion.
-fn verify_bisection_inner<H, C, L, R>(
+/// Verify the given target height using the states held by the store,
+/// writing the newly verified states to it like [`verify_bisection_in_store`].
+///
+/// If the store already holds a trusted or verified state at the target height,
+/// it is returned without making any request.
+/// Otherwise the target is verified with [`verify_trace_to_height`].
+///
+/// Returns the errors of [`verify_trace_to_height`].
+pub fn verify_to_height<C, H, L, V, R, S, K>(
+    store: &mut S,
+    target: Height,
+    options: &Options<L, V>,
+    clock: &K,
+    req: &R,
+) -> Result<TrustedState<C, H>, Error>
+    where
+        H: Header,
+        C: Commit,
+        L: TrustThreshold,
+        V: VerificationPredicates,
+        R: Requester<C, H>,
+        S: LightStore<C, H>,
+        K: Clock,
+{
+    if let Some((state, status)) = store.get(target) {
+        if status.is_trusted_or_verified() {
+            return Ok(state);
+        }
+    }
+
+    let mut history = ChurnHistory::new(DEFAULT_CHURN_HISTORY_LEN);
+    let mut trace = verify_trace_to_height(store, target, options, clock, req, &mut history)?;
+    match trace.pop() {
+        Some(state) => Ok(state),
+        None => Err(Kind::ImplementationSpecific.context("empty verification trace")),
+    }
+}
+
+/// Verify the given target height from the states held by the store, writing
+/// the newly verified states to it like [`verify_bisection_in_store`], even if
+/// the store already holds a state at the target height.
+///
+/// We bisect from the highest trusted or verified state below the target
+/// which has not expired. Expired states are skipped, falling back to the next
+/// lower candidate.
+///
+/// On success, returns the trace which was verified: the state we bisected from,
+/// followed by the newly verified states, the last one being at the target height.
+/// This is the primary trace expected by [`super::fork_detector::detect_forks`].
+///
+/// The churn history is used as by [`verify_bisection_with_history`].
+///
+/// Returns the [`Kind::Expired`] error of the highest candidate if all candidates
+/// expired, [`Kind::NoTrustedState`] if there is no candidate at all, and the errors
+/// of [`verify_bisection`] otherwise.
+pub fn verify_trace_to_height<C, H, L, V, R, S, K>(
+    store: &mut S,
+    target: Height,
+    options: &Options<L, V>,
+    clock: &K,
+    req: &R,
+    history: &mut ChurnHistory,
+) -> Result<Vec<TrustedState<C, H>>, Error>
+    where
+        H: Header,
+        C: Commit,
+        L: TrustThreshold,
+        V: VerificationPredicates,
+        R: Requester<C, H>,
+        S: LightStore<C, H>,
+        K: Clock,
+{
+    let mut below = target;
+    let mut expired: Option<Error> = None;
+    loop {
+        let anchor = match store.highest_below(below) {
+            Some(anchor) => anchor,
+            None => return Err(match expired {
+                Some(e) => e,
+                None => Kind::NoTrustedState { height: target }.into(),
+            }),
+        };
+        let anchor_header = anchor.last_header().header();
+        match is_within_trust_period(anchor_header, options.trusting_period(), clock.now()) {
+            Ok(()) => {
+                let new_states = verify_bisection_with_history(
+                    anchor.clone(),
+                    target,
+                    options,
+                    clock,
+                    req,
+                    history,
+                )?;
+                let mut trace = Vec::with_capacity(new_states.len().saturating_add(1));
+                trace.push(anchor);
+                trace.extend_from_slice(&new_states);
+                store_verified(store, new_states)?;
+                return Ok(trace);
+            }
+            Err(e) => {
+                match e.kind() {
+                    Kind::Expired { .. } => (),
+                    _ => return Err(e),
+                }
+                below = anchor_header.height();
+                if expired.is_none() {
+                    expired = Some(e.at_height(below));
+                }
+            }
+        }
+    }
+}
+
+/// Maximum number of times [`verify_to_highest`] follows a latest height which
+/// moved while it was syncing to the previous one.
+const MAX_LATEST_HEIGHT_ROUNDS: usize = 3;
+
+/// Verify the latest block known to the provider using the states held by the store,
+/// like [`verify_to_height`] does for a given target height.
+///
+/// The chain keeps growing while we sync, so once the latest height has been
+/// verified the provider is asked for it again. If it moved, we continue to the
+/// newer height from the state we just verified. As a chain producing blocks faster
+/// than we verify them could keep us busy forever, this is done at most
+/// [`MAX_LATEST_HEIGHT_ROUNDS`] times: the returned state is then the last one verified.
+/// If the provider reports a latest height which is not above the highest
+/// trusted or verified state of the store, that state is returned without
+/// making any further request.
+pub fn verify_to_highest<C, H, L, V, R, S, K>(
+    store: &mut S,
+    options: &Options<L, V>,
+    clock: &K,
+    req: &R,
+) -> Result<TrustedState<C, H>, Error>
+    where
+        H: Header,
+        C: Commit,
+        L: TrustThreshold,
+        V: VerificationPredicates,
+        R: Requester<C, H>,
+        S: LightStore<C, H>,
+        K: Clock,
+{
+    let mut target = req.latest_height()?;
+    if let Some(highest) = store.highest_below(Height::max_value()) {
+        if target <= highest.last_header().header().height() {
+            return Ok(highest);
+        }
+    }
+    is_not_lagging(&req.signed_header(target)?, options.max_block_lag(), clock.now())?;
+    let mut verified = verify_to_height(
+        store,
+        target,
+        options,
+        clock,
+        req,
+    )?;
+    let mut rounds = 1;
+    while rounds < MAX_LATEST_HEIGHT_ROUNDS {
+        let latest = req.latest_height()?;
+        if latest <= target {
+            break;
+        }
+        target = latest;
+        is_not_lagging(&req.signed_header(target)?, options.max_block_lag(), clock.now())?;
+        verified = verify_to_height(
+            store,
+            target,
+            options,
+            clock,
+            req,
+        )?;
+        rounds += 1;
+    }
+    Ok(verified)
+}
+
+// Returns an error if the latest header of a provider is older than
+// now - max_block_lag, ie. the provider is not keeping up with the chain.
+fn is_not_lagging<C, H>(
+    latest: &SignedHeader<C, H>,
+    max_block_lag: Duration,
+    now: SystemTime,
+) -> Result<(), Error>
+    where
+        H: Header,
+        C: Commit,
+{
+    let header = latest.header();
+    // if header time + max_block_lag is not representable, it is after now
+    if let Some(lag_limit) = header.bft_time().checked_add(max_block_lag) {
+        if lag_limit < now {
+            return Err(Kind::LaggingProvider {
+                height: header.height(),
+            }.into());
+        }
+    }
+    Ok(())
+}
+
+// Write the states returned by verify_bisection to the store
+// and return the last one, ie. the state at the target height.
+fn store_verified<C, H, S>(
+    store: &mut S,
+    mut new_states: Vec<TrustedState<C, H>>,
+) -> Result<TrustedState<C, H>, Error>
+    where
+        H: Header,
+        C: Commit,
+        S: LightStore<C, H>,
+{
+    // the state at the target height is the last one to be verified
+    let last = match new_states.pop() {
+        Some(last) => last,
+        None => return Err(Kind::ImplementationSpecific.context("no verified state to store")),
+    };
+    while let Some(state) = new_states.pop() {
+        store.insert(state, Status::Verified)?;
+    }
+    store.insert(last.clone(), Status::Verified)?;
+    Ok(last)
+}
+
+// Outcome of a skip attempt from a trusted state to an untrusted height.
+enum Skip<C, H>
+where
+    C: Commit,
+    H: Header,
+{
+    // The untrusted height verified: the new state to be trusted.
+    Verified(TrustedState<C, H>),
+    // Not enough of the trusted validators signed, or are still validators,
+    // at the untrusted height. Never the case for adjacent heights.
+    NotEnoughTrust,
+}
+
+// Try to skip from trusted_state to untrusted_height, fetching what is needed
+// from req. Also returns the overlap of the trusted validators with the
+// validators at untrusted_height, which tells how much they changed.
+fn try_skip<H, C, L, V, R, K>(
     trusted_state: &TrustedState<C, H>,
     untrusted_height: Height,
-    trust_threshold: L,
+    options: &Options<L, V>,
+    clock: &K,
     req: &R,
-    cache: &mut Vec<TrustedState<C, H>>,
-) -> Result<TrustedState<C, H>, Error>
+) -> Result<(Skip<C, H>, OverlapReport), Error>
     where
         H: Header,
         C: Commit,
         L: TrustThreshold,
+        V: VerificationPredicates,
         R: Requester<C, H>,
+        K: Clock,
 {
-    // // fetch the header and vals for the new height
-    let untrusted_sh = &req.signed_header(untrusted_height)?;
-    let untrusted_vals = &req.validator_set(untrusted_height)?;
+    let trusted_h = trusted_state.last_header().header().height();
+    let inc_trusted_h = match trusted_h.checked_add(1) {
+        Some(inc_trusted_h) => inc_trusted_h,
+        None => return Err(Kind::ImplementationSpecific.context("height overflow")),
+    };
+
+    // fetch the header and vals for the new height
+    let untrusted_sh = &at_height(req.signed_header(untrusted_height), untrusted_height)?;
+    is_not_from_future(untrusted_sh.header(), options.max_clock_drift(), clock.now())?;
+    let untrusted_vals = &at_height(req.validator_set(untrusted_height), untrusted_height)?;
+    // the vals must be the ones of the header before we rely on them below,
+    // otherwise a provider could make every skip fail the pre-check
+    at_height(
+        options.predicates().validator_sets_match(untrusted_sh.header(), untrusted_vals),
+        untrusted_height,
+    )?;
+
+    // Cheap pre-check before skipping to a non-adjacent height: if not enough
+    // of the trusted validators are still validators at the new height,
+    // no commit for it can meet the trust threshold, so give up right away
+    // without fetching the next vals nor verifying the commit. The commit
+    // itself came with the signed header, which the vals were checked against.
+    let overlap = trusted_state.validators().overlap(untrusted_vals);
+    if untrusted_height > inc_trusted_h && !overlap.may_meet(options.trust_threshold()) {
+        return Ok((Skip::NotEnoughTrust, overlap));
+    }
+
+    // fetch the next vals for the new height
     let inc_untrusted_height = match untrusted_height.checked_add(1) {
         Some(inc_untrusted_height) => inc_untrusted_height,
-        None => return Err(Kind::ImplementationSpecific),
+        None => {
+            return Err(Kind::ImplementationSpecific
+                .context("height overflow")
+                .at_height(untrusted_height))
+        }
     };
-    let untrusted_next_vals = &req.validator_set(inc_untrusted_height)?;
+    let untrusted_next_vals = &at_height(
+        req.validator_set(inc_untrusted_height),
+        untrusted_height,
+    )?;
 
     // check if we can skip to this height and if it verifies.
     match verify_single_inner(
@@ -347,31 +855,65 @@ fn verify_bisection_inner<H, C, L, R>(
         untrusted_sh,
         untrusted_vals,
         untrusted_next_vals,
-        trust_threshold,
+        options,
     ) {
-        Ok(_) => {
+        Verdict::Success => {
             // Successfully verified!
-            // memoize the new to be trusted state and return.
+            // make sure the trusted state did not expire while we were at it.
+            let trusted_header = trusted_state.last_header().header();
+            at_height(
+                is_within_trust_period(trusted_header, options.trusting_period(), clock.now()),
+                untrusted_height,
+            )?;
             let ts = TrustedState::new(untrusted_sh, untrusted_next_vals);
+            Ok((Skip::Verified(ts), overlap))
+        }
+        // Insufficient voting power to update.
+        Verdict::NotEnoughTrust(_) => Ok((Skip::NotEnoughTrust, overlap)),
+        // If something went wrong, return the error.
+        Verdict::Invalid(e) => Err(e.at_height(untrusted_height)),
+    }
+}
+
+// inner recursive function for verify_and_update_bisection.
+// see that function's docs.
+// A cache is passed in to memoize all new states to be trusted.
+// Note: we only write to the cache and it guarantees that we do
+// not store states twice.
+// Additionally, a new state is returned for convenience s.t. it can
+// be used for the other half of the recursion.
+fn verify_bisection_inner<H, C, L, V, R, K>(
+    trusted_state: &TrustedState<C, H>,
+    untrusted_height: Height,
+    options: &Options<L, V>,
+    clock: &K,
+    req: &R,
+    cache: &mut Vec<TrustedState<C, H>>,
+) -> Result<TrustedState<C, H>, Error>
+    where
+        H: Header,
+        C: Commit,
+        L: TrustThreshold,
+        V: VerificationPredicates,
+        R: Requester<C, H>,
+        K: Clock,
+{
+    match try_skip(trusted_state, untrusted_height, options, clock, req)? {
+        // memoize the new to be trusted state and return.
+        (Skip::Verified(ts), _) => {
             cache.push(ts.clone());
             return Ok(ts);
         }
-        Err(e) => {
-            match e {
-                // Insufficient voting power to update.
-                // Engage bisection, below.
-                Kind::InsufficientVotingPower { .. } => (),
-                // If something went wrong, return the error.
-                real_err => return Err(real_err),
-            }
-        }
+        // Engage bisection, below.
+        (Skip::NotEnoughTrust, _) => (),
     }
+
     // Get the pivot height for bisection.
     let trusted_h = trusted_state.last_header().header().height();
     let untrusted_h = untrusted_height;
     let sum = match trusted_h.checked_add(untrusted_h) {
         Some(sum) => sum,
-        None => return Err(Kind::ImplementationSpecific),
+        None => return Err(Kind::ImplementationSpecific.context("height overflow")),
     };
     let pivot_height = sum / 2;
     // Recursive call to bisect to the pivot height.
@@ -380,7 +922,8 @@ fn verify_bisection_inner<H, C, L, R>(
     let trusted_left = verify_bisection_inner(
         trusted_state,
         pivot_height,
-        trust_threshold,
+        options,
+        clock,
         req,
         cache,
     )?;
@@ -389,8 +932,203 @@ fn verify_bisection_inner<H, C, L, R>(
     verify_bisection_inner(
         &trusted_left,
         untrusted_height,
-        trust_threshold,
+        options,
+        clock,
         req,
         cache,
     )
-}
\ No newline at end of file
+}
+
+// inner function for verify_bisection in adaptive mode:
+// instead of trying untrusted_height first, each skip goes as far as the
+// validator churn recorded in the history suggests the trust threshold allows.
+// On failure, the target is lowered to the closest of that guess and of
+// the bisection pivot.
+// All new states to be trusted are pushed to the cache.
+fn verify_adaptive_inner<H, C, L, V, R, K>(
+    trusted_state: &TrustedState<C, H>,
+    untrusted_height: Height,
+    options: &Options<L, V>,
+    clock: &K,
+    req: &R,
+    cache: &mut Vec<TrustedState<C, H>>,
+    history: &mut ChurnHistory,
+) -> Result<(), Error>
+    where
+        H: Header,
+        C: Commit,
+        L: TrustThreshold,
+        V: VerificationPredicates,
+        R: Requester<C, H>,
+        K: Clock,
+{
+    let trusted_height = trusted_state.last_header().header().height();
+    let inc_trusted_height = match trusted_height.checked_add(1) {
+        Some(inc_trusted_height) => inc_trusted_height,
+        None => return Err(Kind::ImplementationSpecific.context("height overflow")),
+    };
+    if untrusted_height < inc_trusted_height {
+        return Err(Kind::NonIncreasingHeight {
+            got: untrusted_height,
+            expected: inc_trusted_height,
+        }.into());
+    }
+
+    let mut current = trusted_state.clone();
+    let mut h = trusted_height;
+    while h < untrusted_height {
+        let mut target = history.farthest_target(h, untrusted_height, options.trust_threshold());
+        loop {
+            let (skip, overlap) = try_skip(&current, target, options, clock, req)?;
+            history.record(&overlap, h, target);
+            match skip {
+                Skip::Verified(ts) => {
+                    cache.push(ts.clone());
+                    current = ts;
+                    h = target;
+                    break;
+                }
+                Skip::NotEnoughTrust => {
+                    // adjacent heights have enough trust, so target > h + 1
+                    // and both the pivot and the guess are in (h, target).
+                    let distance = match target.checked_sub(h) {
+                        Some(distance) if distance >= 2 => distance,
+                        _ => {
+                            return Err(Kind::ImplementationSpecific
+                                .context("not enough trust at an adjacent height")
+                                .at_height(target))
+                        }
+                    };
+                    let pivot = h.saturating_add(distance / 2);
+                    let below_target = target.saturating_sub(1);
+                    let guess = history.farthest_target(h, below_target, options.trust_threshold());
+                    target = if guess < pivot { guess } else { pivot };
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// inner function for verify_bisection in sequential mode:
+// verifies every height from the trusted one up to untrusted_height,
+// each against the state verified at the previous height.
+// All new states to be trusted are pushed to the cache.
+fn verify_sequential_inner<H, C, L, V, R, K>(
+    trusted_state: &TrustedState<C, H>,
+    untrusted_height: Height,
+    options: &Options<L, V>,
+    clock: &K,
+    req: &R,
+    cache: &mut Vec<TrustedState<C, H>>,
+) -> Result<(), Error>
+    where
+        H: Header,
+        C: Commit,
+        L: TrustThreshold,
+        V: VerificationPredicates,
+        R: Requester<C, H>,
+        K: Clock,
+{
+    let trusted_height = trusted_state.last_header().header().height();
+    let inc_trusted_height = match trusted_height.checked_add(1) {
+        Some(inc_trusted_height) => inc_trusted_height,
+        None => return Err(Kind::ImplementationSpecific.context("height overflow")),
+    };
+    if untrusted_height < inc_trusted_height {
+        return Err(Kind::NonIncreasingHeight {
+            got: untrusted_height,
+            expected: inc_trusted_height,
+        }.into());
+    }
+
+    let mut current = trusted_state.clone();
+    let mut h = trusted_height;
+    while h < untrusted_height {
+        h += 1;
+        let untrusted_sh = &at_height(req.signed_header(h), h)?;
+        is_not_from_future(untrusted_sh.header(), options.max_clock_drift(), clock.now())?;
+        let untrusted_vals = &at_height(req.validator_set(h), h)?;
+        let inc_h = match h.checked_add(1) {
+            Some(inc_h) => inc_h,
+            None => return Err(Kind::ImplementationSpecific.context("height overflow").at_height(h)),
+        };
+        let untrusted_next_vals = &at_height(req.validator_set(inc_h), h)?;
+
+        let verdict = verify_single_inner(
+            &current,
+            untrusted_sh,
+            untrusted_vals,
+            untrusted_next_vals,
+            options,
+        );
+        at_height(verdict.into_result(), h)?;
+        let current_header = current.last_header().header();
+        at_height(
+            is_within_trust_period(current_header, options.trusting_period(), clock.now()),
+            h,
+        )?;
+        current = TrustedState::new(untrusted_sh, untrusted_next_vals);
+        cache.push(current.clone());
+    }
+    Ok(())
+}
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use lite::error::Kind;
+    use lite::test_utils::TestHeader;
+
+    use super::{is_not_from_future, is_within_trust_period};
+
+    fn header(height: u64) -> TestHeader {
+        TestHeader {
+            height,
+            every: 1,
+            fork: 0,
+        }
+    }
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn an_unrepresentable_expiration_is_an_error() {
+        let longest = Duration::new(u64::max_value(), 999_999_999);
+        match is_within_trust_period(&header(10), longest, at(20)) {
+            Err(e) => match e.kind() {
+                Kind::ExpirationOutOfRange { trusting_period, .. } => {
+                    assert_eq!(*trusting_period, longest)
+                }
+                kind => panic!("unexpected error {:?}", kind),
+            },
+            Ok(()) => panic!("no error for an expiration out of range"),
+        }
+    }
+
+    #[test]
+    fn headers_from_the_far_future_are_rejected() {
+        let far = 1 << 40;
+        let trusting_period = Duration::from_secs(100);
+        match is_within_trust_period(&header(far), trusting_period, at(20)) {
+            Err(e) => match e.kind() {
+                Kind::DurationOutOfRange => (),
+                kind => panic!("unexpected error {:?}", kind),
+            },
+            Ok(()) => panic!("trusted a header from the future"),
+        }
+        assert!(is_within_trust_period(&header(far), trusting_period, at(far + 1)).is_ok());
+
+        let drift = Duration::from_secs(10);
+        match is_not_from_future(&header(far), drift, at(20)) {
+            Err(e) => match e.kind() {
+                Kind::HeaderFromFuture { height } => assert_eq!(*height, far),
+                kind => panic!("unexpected error {:?}", kind),
+            },
+            Ok(()) => panic!("accepted a header from the future"),
+        }
+        assert!(is_not_from_future(&header(far), drift, at(far - 10)).is_ok());
+    }
+}
@@ -3,6 +3,7 @@ extern crate prusti_contracts;
 pub mod lite;
 pub mod error;
 pub mod hash;
+pub mod account;
 
 #[trusted]
 fn main() {}